@@ -1,5 +1,5 @@
 use std::fs::File;
-use std::io::{self, prelude::*, BufReader};
+use std::io::{self, prelude::*, BufReader, BufWriter};
 use std::vec;
 use clap::Parser;
 
@@ -15,9 +15,17 @@ struct Args {
     /// Input file
     #[arg(short, long = "input-path")]
     input_path: String,
+
+    /// Draw the maze with box-drawing characters and colors
+    #[arg(short, long)]
+    render: bool,
+
+    /// Export the drawing to this SVG file
+    #[arg(short, long = "output-path")]
+    output_path: Option<String>,
 }
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 enum Dir {
     North,
     East,
@@ -26,6 +34,18 @@ enum Dir {
 }
 
 
+impl Dir {
+    fn opposite(&self) -> Dir {
+        match self {
+            Dir::North => Dir::South,
+            Dir::East => Dir::West,
+            Dir::West => Dir::East,
+            Dir::South => Dir::North,
+        }
+    }
+}
+
+
 fn main() -> io::Result<()>{
     let args = Args::parse();
     let file = File::open(args.input_path)?;
@@ -35,39 +55,122 @@ fn main() -> io::Result<()>{
     let mut m = 0;
     for line in reader.lines() {
         let mut row: Vec<char> = line?.chars().collect();
-        if map.len() == 0 {
+        if map.is_empty() {
             m = row.len();
             map.push(vec!['.'; m + 2]);
         }
+        if row.len() != m {
+            return Err(invalid(format!("row {} has {} tiles instead of {}", map.len() - 1, row.len(), m)));
+        }
         map.push(vec!['.']);
         let n = map.len();
         map[n-1].append(&mut row);
         map[n-1].push('.');
     }
     map.push(vec!['.'; m + 2]);
-    let start = get_start(&map);
-    let (loop_dir1, loop_dir2) = get_loop_dir(&map, &start);
-    clean_loop(&mut map, &start, loop_dir1);
-    mark_start(&mut map, &start, loop_dir1, loop_dir2);
+    let original = map.clone();
+    let start = get_start(&map).ok_or_else(|| invalid(String::from("no start tile S")))?;
+    let (loop_dir, start_pipe) = infer_start(&map, &start).map_err(invalid)?;
+    mark_start(&mut map, &start, start_pipe);
+    let path = trace_loop(&map, &start, loop_dir).map_err(invalid)?;
+    clean_loop(&mut map, &path);
     mark_inside(&mut map);
     let res = count_inside(&map);
-    for row in map {
-        println!("{:?}", row.into_iter().collect::<String>());
+    if args.render {
+        render(&map, &original);
+    }
+    if let Some(path_svg) = args.output_path {
+        save_svg(&map, &original, &path, &path_svg)?;
     }
+    let pick = count_inside_pick(&path);
+    if pick != res as i64 {
+        println!("Ray casting counts {} inside tiles but Pick's theorem gives {}", res, pick);
+    }
+    println!("{}", path.len() / 2);
     println!("{}", res);
     Ok(())
 }
 
 
-fn count_inside(map: &Vec<Vec<char>>) -> u32 {
+fn box_char(pipe: char) -> char {
+    match pipe {
+        '|' => '│',
+        '-' => '─',
+        'L' => '└',
+        'J' => '┘',
+        '7' => '┐',
+        'F' => '┌',
+        _ => pipe,
+    }
+}
+
+
+fn render(map: &[Vec<char>], original: &[Vec<char>]) {  // Padding is not drawn
+    for i in 1..map.len()-1 {
+        let mut line = String::new();
+        for j in 1..map[0].len()-1 {
+            line += &match (map[i][j], original[i][j]) {
+                ('I', '.') => String::from("\x1b[32m·\x1b[0m"),
+                ('I', junk) => format!("\x1b[33m{}\x1b[0m", box_char(junk)),
+                ('O', '.') => String::from("\x1b[34m·\x1b[0m"),
+                ('O', junk) => format!("\x1b[90m{}\x1b[0m", box_char(junk)),
+                (pipe, _) => format!("\x1b[1m{}\x1b[0m", box_char(pipe)),
+            };
+        }
+        println!("{}", line);
+    }
+}
+
+
+fn save_svg(map: &[Vec<char>], original: &[Vec<char>], path: &[(usize, usize)], output_path: &str) -> io::Result<()> {
+    const CELL: usize = 10;
+    let (n, m) = (map.len() - 2, map[0].len() - 2);
+    let f = File::create(output_path)?;
+    let mut out = BufWriter::new(f);
+    out.write_fmt(format_args!("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\">\n", m * CELL, n * CELL))?;
+    out.write_fmt(format_args!("  <rect width=\"100%\" height=\"100%\" fill=\"#1e1e2e\"/>\n"))?;
+    for i in 1..=n {
+        for j in 1..=m {
+            let fill = match (map[i][j], original[i][j]) {
+                ('I', '.') => "#40a02b",
+                ('I', _) => "#df8e1d",
+                ('O', '.') => continue,
+                ('O', _) => "#585b70",
+                _ => continue,
+            };
+            out.write_fmt(format_args!("  <rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"{}\"/>\n",
+                (j - 1) * CELL, (i - 1) * CELL, CELL, CELL, fill))?;
+        }
+    }
+    let points: Vec<String> = path.iter()
+        .map(|u| format!("{},{}", (u.1 - 1) * CELL + CELL / 2, (u.0 - 1) * CELL + CELL / 2))
+        .collect();
+    out.write_fmt(format_args!("  <polygon points=\"{}\" fill=\"none\" stroke=\"#f5e0dc\" stroke-width=\"{}\"/>\n",
+        points.join(" "), CELL / 3))?;
+    out.write_fmt(format_args!("</svg>\n"))?;
+    Ok(())
+}
+
+
+fn count_inside(map: &[Vec<char>]) -> u32 {
     map
         .iter()
         .map(|x| x.iter().filter(|&&y| y == 'I').count() as u32)
-        .fold(0, |acc, x| acc + x)
+        .sum()
+}
+
+
+fn count_inside_pick(path: &[(usize, usize)]) -> i64 {  // Shoelace area, then Pick's theorem A = i + b/2 - 1
+    let mut twice_area: i64 = 0;
+    for (k, u) in path.iter().enumerate() {
+        let v = path[(k + 1) % path.len()];
+        twice_area += u.1 as i64 * v.0 as i64 - v.1 as i64 * u.0 as i64;
+    }
+    (twice_area.abs() - path.len() as i64) / 2 + 1
 }
 
 
-fn mark_inside(map: &mut Vec<Vec<char>>) {
+fn mark_inside(map: &mut [Vec<char>]) {
     for i in 0..map.len() {
         for j in 0..map[0].len() {
             if map[i][j] == '.' {
@@ -78,24 +181,24 @@ fn mark_inside(map: &mut Vec<Vec<char>>) {
 }
 
 
-fn check_inside(map: &Vec<Vec<char>>, pos: &(usize, usize)) -> char {
+fn check_inside(map: &[Vec<char>], pos: &(usize, usize)) -> char {
     let mut crosses = 0;
-    let mut is_L = false;
-    let mut is_J = false;
-    let mut curr = pos.clone();
+    let mut is_l = false;
+    let mut is_j = false;
+    let mut curr = *pos;
     while map[curr.0][curr.1] != 'I' && map[curr.0][curr.1] != 'O' {
         curr = avance(&curr, Dir::North);
         match map[curr.0][curr.1] {
             '-' => crosses += 1,
-            'L' => is_L = true,
-            'J' => is_J = true,
+            'L' => is_l = true,
+            'J' => is_j = true,
             '7' => {
-                if is_L { crosses += 1; is_L = false; }
-                else { is_J = false; }
+                if is_l { crosses += 1; is_l = false; }
+                else { is_j = false; }
             },
             'F' => {
-                if is_J { crosses += 1; is_J = false; }
-                else { is_L = false; }
+                if is_j { crosses += 1; is_j = false; }
+                else { is_l = false; }
             },
             _ => {},
         };
@@ -112,119 +215,85 @@ fn check_inside(map: &Vec<Vec<char>>, pos: &(usize, usize)) -> char {
 }
 
 
-fn mark_start(map: &mut Vec<Vec<char>>, start: &(usize, usize), loop_dir1: Dir, loop_dir2: Dir) {
-    map[start.0][start.1] = match (
-        loop_dir1, loop_dir2
-    ) {
-        (Dir::North, Dir::North) => '|',
-        (Dir::North, Dir::East) => 'J',
-        (Dir::North, Dir::West) => 'L',
-        (Dir::South, Dir::South) => '|',
-        (Dir::South, Dir::East) => '7',
-        (Dir::South, Dir::West) => 'F',
-        (Dir::East, Dir::East) => '-',
-        (Dir::East, Dir::North) => 'F',
-        (Dir::East, Dir::South) => 'L',
-        (Dir::West, Dir::West) => '-',
-        (Dir::West, Dir::North) => '7',
-        (Dir::West, Dir::South) => 'J',
-        _ => panic!(),
-    };
+fn mark_start(map: &mut [Vec<char>], start: &(usize, usize), pipe: char) {
+    map[start.0][start.1] = pipe;
 }
 
 
-fn clean_loop(map: &mut Vec<Vec<char>>, start: &(usize, usize), loop_dir: Dir) {
+fn clean_loop(map: &mut Vec<Vec<char>>, path: &[(usize, usize)]) {
     let mut clean = vec![vec!['.'; map[0].len()]; map.len()];
-    let mut vis: Vec<Vec<bool>> = vec![vec![false; map[0].len()]; map.len()];
-    // println!("{:?}", vis);
-    let mut u = start.clone();
-    u = avance(&u, loop_dir);
-    vis[start.0][start.1] = true;
-    while u != *start {
+    for u in path {
         clean[u.0][u.1] = map[u.0][u.1];
-        // println!("{:?}", u);
-        if map[u.0][u.1] == '.' { panic!(); }
-        vis[u.0][u.1] = true;
-        (u, _) = get_next(&map, &vis, &u);
     }
-    for i in 0..map.len() {
-        clean[i][0] = 'O';
-        clean[i][map[0].len()-1] = 'O';
-    }
-    for j in 0..map[0].len() {
-        clean[0][j] = 'O';
-        clean[map.len()-1][j] = 'O';
+    let m = map[0].len();
+    for row in clean.iter_mut() {
+        row[0] = 'O';
+        row[m-1] = 'O';
     }
+    let n = clean.len();
+    clean[0].fill('O');
+    clean[n-1].fill('O');
     *map = clean;
 }
 
 
-fn get_loop_dir(map: &Vec<Vec<char>>, start: &(usize, usize)) -> (Dir, Dir) {
-    let mut max_len: u32 = 0;
-    let mut max_dir: Dir = Dir::North;
-    let mut other_dir: Dir = Dir::North;
-    for dir in [Dir::North, Dir::East, Dir::West, Dir::South] {
-        let mut len = 1;
-        let mut vis: Vec<Vec<bool>> = vec![vec![false; map[0].len()]; map.len()];
-        // println!("{:?}", vis);
-        let mut u = start.clone();
+fn infer_start(map: &[Vec<char>], start: &(usize, usize)) -> Result<(Dir, char), String> {
+    let connected: Vec<Dir> = [Dir::North, Dir::East, Dir::West, Dir::South]
+        .into_iter()
+        .filter(|&dir| {
+            let u = avance(start, dir);
+            openings(map[u.0][u.1]).is_some_and(|o| o.contains(&dir.opposite()))
+        })
+        .collect();
+    let (row, col) = (start.0 - 1, start.1 - 1);  // Without padding
+    match connected[..] {
+        [a, b] => Ok((a, pipe_from(a, b))),
+        [] => Err(format!("no pipe connects to S at row {}, column {}", row, col)),
+        [dir] => Err(format!("only the pipe to the {:?} connects to S at row {}, column {}", dir, row, col)),
+        _ => Err(format!("{} pipes connect to S at row {}, column {}: {:?}", connected.len(), row, col, connected)),
+    }
+}
+
+
+fn trace_loop(map: &[Vec<char>], start: &(usize, usize), dir: Dir) -> Result<Vec<(usize, usize)>, String> {
+    let mut path = vec![*start];  // Loop tiles in walking order
+    let (mut u, mut dir) = (avance(start, dir), dir);
+    while u != *start {
+        let next = openings(map[u.0][u.1])
+            .filter(|o| o.contains(&dir.opposite()))
+            .map(|o| if o[0] == dir.opposite() { o[1] } else { o[0] });
+        dir = match next {
+            Some(next) => next,
+            None => return Err(format!(
+                "the loop breaks at row {}, column {}: '{}' does not connect to the {:?}",
+                u.0 - 1, u.1 - 1, map[u.0][u.1], dir.opposite()
+            )),
+        };
+        path.push(u);
         u = avance(&u, dir);
-        vis[start.0][start.1] = true;
-        let mut aux_dir: Dir = Dir::North;
-        while u != *start {
-            // println!("{:?}", u);
-            if map[u.0][u.1] == '.' { len = 0; break; }
-            vis[u.0][u.1] = true;
-            (u, aux_dir) = get_next(&map, &vis, &u);
-            len += 1;
-        }
-        if len > max_len {
-            max_len = len;
-            max_dir = dir;
-            other_dir = aux_dir;
-        }
     }
-    return (max_dir, other_dir);
+    Ok(path)
 }
 
 
-fn get_next(map: &Vec<Vec<char>>, vis: &Vec<Vec<bool>>, u: &(usize, usize)) -> ((usize, usize), Dir) {
-    let pipe = map[u.0][u.1];
-    let (dir1, dir2);
+fn openings(pipe: char) -> Option<[Dir; 2]> {
     match pipe {
-        '|' => {
-            dir1 = Dir::North;
-            dir2 = Dir::South;
-        },
-        '-' => {
-            dir1 = Dir::East;
-            dir2 = Dir::West;
-        },
-        'L' => {
-            dir1 = Dir::North;
-            dir2 = Dir::East;
-        },
-        'J' => {
-            dir1 = Dir::North;
-            dir2 = Dir::West;
-        },
-        '7' => {
-            dir1 = Dir::West;
-            dir2 = Dir::South;
-        },
-        'F' => {
-            dir1 = Dir::East;
-            dir2 = Dir::South;
-        },
-        _ => panic!(),
-    };
-    let u1 = avance(u, dir1);
-    let u2 = avance(u, dir2);
-    if !vis[u1.0][u1.1] { return (u1, dir1); }
-    if !vis[u2.0][u2.1] { return (u2, dir2); }
-    if map[u1.0][u1.1] == 'S' { return (u1, dir1); }
-    if map[u2.0][u2.1] == 'S' { return (u2, dir2); }
-    panic!();
+        '|' => Some([Dir::North, Dir::South]),
+        '-' => Some([Dir::East, Dir::West]),
+        'L' => Some([Dir::North, Dir::East]),
+        'J' => Some([Dir::North, Dir::West]),
+        '7' => Some([Dir::West, Dir::South]),
+        'F' => Some([Dir::East, Dir::South]),
+        _ => None,
+    }
+}
+
+
+fn pipe_from(a: Dir, b: Dir) -> char {
+    ['|', '-', 'L', 'J', '7', 'F']
+        .into_iter()
+        .find(|&pipe| openings(pipe).is_some_and(|o| o.contains(&a) && o.contains(&b)))
+        .unwrap()
 }
 
 
@@ -238,13 +307,18 @@ fn avance(u: &(usize, usize), dir: Dir) -> (usize, usize) {
 }
 
 
-fn get_start(map: &Vec<Vec<char>>) -> (usize, usize) {
+fn get_start(map: &[Vec<char>]) -> Option<(usize, usize)> {
     for (i, row) in map.iter().enumerate() {
         for (j, value) in row.iter().enumerate() {
             if *value == 'S' {
-                return (i, j);
+                return Some((i, j));
             }
         }
     }
-    return (0, 0);
+    None
+}
+
+
+fn invalid(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
 }
@@ -15,6 +15,22 @@ struct Args {
     /// Input file
     #[arg(short, long = "input-path")]
     input_path: String,
+
+    /// Number of rows or columns every empty one becomes
+    #[arg(short, long, default_value_t = 1000000, value_parser = clap::value_parser!(u64).range(1..))]
+    expansion: u64,
+
+    /// Print the expanded coordinates of every galaxy
+    #[arg(short, long)]
+    coords: bool,
+
+    /// Print the distance between two galaxies, numbered from 1
+    #[arg(short, long, num_args = 2, value_names = ["A", "B"])]
+    distance: Option<Vec<usize>>,
+
+    /// Print the nearest and farthest pairs of galaxies
+    #[arg(short = 'x', long)]
+    extremes: bool,
 }
 
 
@@ -30,71 +46,119 @@ fn main() -> io::Result<()> {
     }
     let dot_rows = get_dot_rows(&board);
     let dot_cols = get_dot_cols(&board);
-    let galaxies = get_galaxies(&board);
-    let distances = get_distances(&galaxies, &dot_rows, &dot_cols);
-    let sum = distances.into_iter().fold(0, |acc, x| acc + x);
+    let overflow = || io::Error::new(io::ErrorKind::InvalidInput, format!("expansion {} overflows 64 bits", args.expansion));
+    let galaxies = expand_galaxies(&get_galaxies(&board), &dot_rows, &dot_cols, args.expansion).ok_or_else(overflow)?;
+    let sum = axis_distances(galaxies.iter().map(|g| g.0).collect())
+        .zip(axis_distances(galaxies.iter().map(|g| g.1).collect()))
+        .and_then(|(rows, cols)| rows.checked_add(cols))
+        .ok_or_else(overflow)?;
+    if args.coords {
+        for (k, g) in galaxies.iter().enumerate() {
+            println!("Galaxy {}: row {}, column {}", k + 1, g.0, g.1);
+        }
+    }
+    if let Some(pair) = args.distance {
+        let (a, b) = (pair[0], pair[1]);
+        if !(1..=galaxies.len()).contains(&a) || !(1..=galaxies.len()).contains(&b) {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("galaxies go from 1 to {}", galaxies.len())));
+        }
+        let d = distance(galaxies[a - 1], galaxies[b - 1]).ok_or_else(overflow)?;
+        println!("Distance between galaxies {} and {}: {}", a, b, d);
+    }
+    if args.extremes && galaxies.len() > 1 {
+        let ((a, b), d) = nearest_pair(&galaxies).ok_or_else(overflow)?;
+        println!("Nearest galaxies: {} and {} at distance {}", a + 1, b + 1, d);
+        let ((a, b), d) = farthest_pair(&galaxies).ok_or_else(overflow)?;
+        println!("Farthest galaxies: {} and {} at distance {}", a + 1, b + 1, d);
+    }
     println!("{}", sum);
     Ok(())
 }
 
 
-fn get_dot_rows(board: &Vec<Vec<char>>) -> Vec<usize> {
+fn get_dot_rows(board: &[Vec<char>]) -> Vec<usize> {
     let mut res = Vec::<usize>::new();
-    for (i, row) in board.into_iter().enumerate() {
-        if all_dots(&row.into_iter().collect()) {
+    for (i, row) in board.iter().enumerate() {
+        if all_dots(row) {
             res.push(i);
         }
     }
-    return res;
+    res
 }
 
 
-fn get_dot_cols(board: &Vec<Vec<char>>) -> Vec<usize> {
-    return get_dot_rows(&transpose(board));
+fn get_dot_cols(board: &[Vec<char>]) -> Vec<usize> {
+    get_dot_rows(&transpose(board))
 }
 
 
-fn get_distances(galaxies: &Vec<(usize, usize)>, dot_rows: &Vec<usize>, dot_cols: &Vec<usize>) -> Vec<usize> {
-    let mut res = Vec::<usize>::new();
-    for (i, &x1) in galaxies.into_iter().enumerate() {
-        for (j, &x2) in galaxies.into_iter().enumerate() {
-            if i < j {
-                let cross_rows = count_crosses(x1.0, x2.0, dot_rows);
-                let cross_cols = count_crosses(x1.1, x2.1, dot_cols);
-                res.push(
-                    (
-                        (x1.0 as i32 - x2.0 as i32).abs() 
-                        + (x1.1 as i32 - x2.1 as i32).abs()
-                        + cross_rows * 999999 + cross_cols * 999999
-                    ) as usize);
+fn expand_galaxies(galaxies: &[(usize, usize)], dot_rows: &[usize], dot_cols: &[usize], expansion: u64) -> Option<Vec<(u64, u64)>> {
+    let expand = |x: usize, dots: &[usize]| {  // Dots are sorted, count the ones before x
+        (dots.partition_point(|&d| d < x) as u64).checked_mul(expansion - 1)?.checked_add(x as u64)
+    };
+    galaxies.iter().map(|&(i, j)| Some((expand(i, dot_rows)?, expand(j, dot_cols)?))).collect()
+}
+
+
+fn distance(a: (u64, u64), b: (u64, u64)) -> Option<u64> {
+    a.0.abs_diff(b.0).checked_add(a.1.abs_diff(b.1))
+}
+
+
+fn nearest_pair(galaxies: &[(u64, u64)]) -> Option<((usize, usize), u64)> {
+    let mut best = ((0, 1), distance(galaxies[0], galaxies[1])?);
+    for i in 0..galaxies.len() {
+        for j in i+1..galaxies.len() {
+            let d = distance(galaxies[i], galaxies[j])?;
+            if d < best.1 {
+                best = ((i, j), d);
             }
         }
     }
-    return res;
+    Some(best)
 }
 
 
-fn count_crosses(x1: usize, x2: usize, list: &Vec<usize>) -> i32 {
-    if x1 == x2 { return 0; }
-    if x1 > x2 { return count_crosses(x2, x1, list); }
-    list.into_iter().filter(|&&x| x1 < x && x < x2).count() as i32
+fn farthest_pair(galaxies: &[(u64, u64)]) -> Option<((usize, usize), u64)> {  // Extremes of x + y and x - y after rotating 45 degrees
+    let mut best = ((0, 0), 0);
+    for key in [|g: (u64, u64)| g.0 as i128 + g.1 as i128, |g: (u64, u64)| g.0 as i128 - g.1 as i128] {
+        let lo = (0..galaxies.len()).min_by_key(|&k| key(galaxies[k])).unwrap();
+        let hi = (0..galaxies.len()).max_by_key(|&k| key(galaxies[k])).unwrap();
+        let d = distance(galaxies[lo], galaxies[hi])?;
+        if d > best.1 {
+            best = ((lo.min(hi), lo.max(hi)), d);
+        }
+    }
+    Some(best)
+}
+
+
+fn axis_distances(mut coords: Vec<u64>) -> Option<u64> {  // Sum over pairs of |a - b| with sorting and prefix sums
+    coords.sort_unstable();
+    let mut prefix: u64 = 0;
+    let mut res: u64 = 0;
+    for (i, &x) in coords.iter().enumerate() {
+        res = res.checked_add(x.checked_mul(i as u64)? - prefix)?;  // Sorted, so x * i >= prefix
+        prefix = prefix.checked_add(x)?;
+    }
+    Some(res)
 }
 
 
-fn get_galaxies(board: &Vec<Vec<char>>) -> Vec<(usize, usize)> {
+fn get_galaxies(board: &[Vec<char>]) -> Vec<(usize, usize)> {
     let mut res = Vec::<(usize, usize)>::new();
-    for (i, row) in board.into_iter().enumerate() {
-        for (j, value) in row.into_iter().enumerate() {
+    for (i, row) in board.iter().enumerate() {
+        for (j, value) in row.iter().enumerate() {
             if *value == '#' {
                 res.push((i, j));
             }
         }
     }
-    return res;
+    res
 }
 
 
-fn transpose<T>(v: &Vec<Vec<T>>) -> Vec<Vec<T>>
+fn transpose<T>(v: &[Vec<T>]) -> Vec<Vec<T>>
 where
     T: Clone,
 {
@@ -105,10 +169,6 @@ where
 }
 
 
-fn all_dots(line: &String) -> bool {
-    let num_dots = line
-        .chars()
-        .filter(|&x| x == '.')
-        .count();
-    num_dots == line.len()
+fn all_dots(line: &[char]) -> bool {
+    line.iter().all(|&x| x == '.')
 }
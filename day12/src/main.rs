@@ -1,6 +1,7 @@
 use std::fs::File;
 use std::io::{self, prelude::*, BufReader};
 use clap::Parser;
+use rayon::prelude::*;
  
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None, help_template = "\
@@ -14,6 +15,14 @@ struct Args {
     /// Input file
     #[arg(short, long = "input-path")]
     input_path: String,
+
+    /// Number of copies of every row after unfolding
+    #[arg(short, long, default_value_t = 5, value_parser = clap::value_parser!(u32).range(1..))]
+    unfold: u32,
+
+    /// Print the number of arrangements of every row
+    #[arg(short, long)]
+    per_row: bool,
 }
  
  
@@ -22,25 +31,28 @@ fn main() -> io::Result<()>{
     let file = File::open(args.input_path)?;
     let reader = BufReader::new(file);
  
-    let mut res = 0;
-    for line in reader.lines() {
-        res += count_damaged(&line?);
+    let lines = reader.lines().collect::<io::Result<Vec<String>>>()?;
+    let counts: Vec<u64> = lines.par_iter().map(|line| count_damaged(line, args.unfold as usize)).collect();
+    if args.per_row {
+        for (line, count) in lines.iter().zip(counts.iter()) {
+            println!("{}: {}", line, count);
+        }
     }
-    println!("{}", res);
+    println!("{}", counts.iter().sum::<u64>());
     Ok(())
 }
  
  
-fn count_damaged(line: &String) -> u64 {
+fn count_damaged(line: &str, unfold: usize) -> u64 {
     let mut text = line
         .split_ascii_whitespace()
-        .nth(0)
+        .next()
         .unwrap()
         .chars()
         .collect::<Vec<char>>();
-    // Repeat 5 times
+    // Repeat unfold times
     let orig = text.clone();
-    for _ in 0..4 {
+    for _ in 1..unfold {
         text.push('?');
         text.append(&mut orig.clone());
     }
@@ -51,21 +63,20 @@ fn count_damaged(line: &String) -> u64 {
         .split(',')
         .map(|x| x.parse::<usize>().unwrap())
         .collect::<Vec<usize>>();
-    // Repeat 5 times
+    // Repeat unfold times
     let orig = nums.clone();
-    for _ in 0..4 {
+    for _ in 1..unfold {
         nums.append(&mut orig.clone());
     }
-    let res = count_ways(&mut text, &nums);
-    return res;
+    count_ways(&text, &nums)
 }
  
-fn count_ways(text: &mut Vec<char>, nums: &Vec<usize>) -> u64 {
+fn count_ways(text: &[char], nums: &[usize]) -> u64 {
     let mut dp = vec![vec![None; nums.len()]; text.len()];
     count_ways_dp(text, nums, 0, 0, &mut dp)
 }
  
-fn count_ways_dp(text: &mut Vec<char>, nums: &Vec<usize>, i: usize, j: usize, dp: &mut Vec<Vec<Option<u64>>>) -> u64 {
+fn count_ways_dp(text: &[char], nums: &[usize], i: usize, j: usize, dp: &mut [Vec<Option<u64>>]) -> u64 {
     if i >= text.len() {  // EOF
         if j >= nums.len() {  // All hashtags are in position
             return 1;
@@ -85,21 +96,19 @@ fn count_ways_dp(text: &mut Vec<char>, nums: &Vec<usize>, i: usize, j: usize, dp
     if j < nums.len() {
         dp[i][j] = Some(res);
     }
-    return res;
+    res
 }
 
 
-fn count_ways_dp_hashtag(text: &mut Vec<char>, nums: &Vec<usize>, i: usize, j: usize, dp: &mut Vec<Vec<Option<u64>>>) -> u64 {
+fn count_ways_dp_hashtag(text: &[char], nums: &[usize], i: usize, j: usize, dp: &mut [Vec<Option<u64>>]) -> u64 {
     if j >= nums.len() {  // No more hashtags
         return 0;
     }
     if i + nums[j] > text.len() {  // Not enough room for hashtags
         return 0;
     }
-    for ii in i..i+nums[j] {
-        if text[ii] == '.' {  // Impossible to fit enough consecutive hashtags
-            return 0;
-        }
+    if text[i..i+nums[j]].contains(&'.') {  // Impossible to fit enough consecutive hashtags
+        return 0;
     }
     // EOF
     if i + nums[j] == text.len() {
@@ -1,5 +1,8 @@
 use std::fs::File;
 use std::io::{self, prelude::*, BufReader};
+use std::str::FromStr;
+use std::collections::BTreeMap;
+use std::fmt;
 use clap::Parser;
 use std::cmp::max;
 
@@ -15,6 +18,141 @@ struct Args {
     /// Input file
     #[arg(short, long = "input-path")]
     input_path: String,
+
+    /// Bag to check the games against, e.g. red=12,green=13,blue=14
+    #[arg(short, long)]
+    bag: Option<Bag>,
+
+    /// Print per game and per color statistics
+    #[arg(short, long)]
+    stats: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Color {
+    Red, Green, Blue
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+struct Bag {
+    red: u32,
+    green: u32,
+    blue: u32,
+}
+
+#[derive(Debug)]
+struct Game {
+    id: u32,
+    draws: Vec<Bag>,
+}
+
+#[derive(Debug)]
+struct Violation {
+    game: u32,
+    draw: usize,  // 1-based index of the draw inside the game
+    color: Color,
+    count: u32,
+    limit: u32,
+}
+
+
+impl Color {
+    const ALL: [Color; 3] = [Color::Red, Color::Green, Color::Blue];
+
+    fn name(&self) -> &'static str {
+        match self {
+            Color::Red => "red",
+            Color::Green => "green",
+            Color::Blue => "blue",
+        }
+    }
+}
+
+
+impl FromStr for Color {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "red" => Ok(Color::Red),
+            "green" => Ok(Color::Green),
+            "blue" => Ok(Color::Blue),
+            _ => Err(format!("unknown color '{}'", s)),
+        }
+    }
+}
+
+
+impl Bag {
+    fn get(&self, color: Color) -> u32 {
+        match color {
+            Color::Red => self.red,
+            Color::Green => self.green,
+            Color::Blue => self.blue,
+        }
+    }
+
+    fn set(&mut self, color: Color, num: u32) {
+        match color {
+            Color::Red => self.red = num,
+            Color::Green => self.green = num,
+            Color::Blue => self.blue = num,
+        }
+    }
+
+    fn power(&self) -> u32 {
+        self.red * self.green * self.blue
+    }
+}
+
+
+impl FromStr for Bag {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut bag = Bag::default();
+        for entry in s.split(',') {
+            let (color, num) = entry.trim().split_once('=')
+                .ok_or(format!("expected color=count, got '{}'", entry))?;
+            let num = num.parse::<u32>().map_err(|e| format!("invalid count '{}': {}", num, e))?;
+            bag.set(color.parse()?, num);
+        }
+        Ok(bag)
+    }
+}
+
+
+impl fmt::Display for Bag {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "red={},green={},blue={}", self.red, self.green, self.blue)
+    }
+}
+
+
+impl Game {
+    fn min_bag(&self) -> Bag {
+        let mut bag = Bag::default();
+        for draw in self.draws.iter() {
+            for color in Color::ALL {
+                bag.set(color, max(bag.get(color), draw.get(color)));
+            }
+        }
+        bag
+    }
+
+    fn violations(&self, bag: &Bag) -> Vec<Violation> {
+        let mut res = vec![];
+        for (i, draw) in self.draws.iter().enumerate() {
+            for color in Color::ALL {
+                if draw.get(color) > bag.get(color) {
+                    res.push(Violation {
+                        game: self.id, draw: i + 1, color, count: draw.get(color), limit: bag.get(color)
+                    });
+                }
+            }
+        }
+        res
+    }
 }
 
 
@@ -23,44 +161,108 @@ fn main() -> io::Result<()>{
     let file = File::open(args.input_path)?;
     let reader = BufReader::new(file);
 
-    let mut sum = 0;
-    for line in reader.lines() {
-        let power = analyze(&line?);
-        sum += power;
+    let mut games = vec![];
+    for (i, line) in reader.lines().enumerate() {
+        let game = parse_game(&line?)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("line {}: {}", i + 1, e)))?;
+        games.push(game);
+    }
+    if args.stats {
+        report_stats(&games);
+    }
+    match args.bag {
+        Some(bag) => report_feasible(&games, &bag),
+        None => println!("{}", games.iter().map(|g| g.min_bag().power()).sum::<u32>()),
     }
-    println!("{}", sum);
     Ok(())
 }
 
 
-fn analyze(line: &String) -> u32 {
-    let game: Vec<&str> = line.split(": ").collect();
-    let mut rm = 0;
-    let mut gm = 0;
-    let mut bm = 0;
-    for play in game[1].split("; "){
-        let (r, g, b) = count_play(play);
-        rm = max(r, rm);
-        gm = max(g, gm);
-        bm = max(b, bm);
+fn report_feasible(games: &[Game], bag: &Bag) {
+    let (feasible, violations) = query_feasible(games, bag);
+    let ids: Vec<String> = feasible.iter().map(|id| id.to_string()).collect();
+    println!("Feasible games: {}", ids.join(" "));
+    println!("Sum of IDs: {}", feasible.iter().sum::<u32>());
+    for v in violations {
+        println!("Game {}: draw {} has {} {} (limit {})", v.game, v.draw, v.count, v.color.name(), v.limit);
     }
-    rm * gm * bm
 }
 
 
-fn count_play(play: &str) -> (u32, u32, u32) {
-    let mut r = 0; let mut g = 0; let mut b = 0;
-    for ball in play.split(", ") {
-        let split: Vec<_> = ball.split(' ').collect();
-        let num = split[0].parse::<u32>().unwrap();
-        let color = split[1];
-        if color == "red" {
-            r = num;
-        } else if color == "green" {
-            g = num;
-        } else {
-            b = num;
+fn report_stats(games: &[Game]) {
+    println!("{:>6} {:>5} {:>5} {:>5} {:>7}", "game", "red", "green", "blue", "power");
+    for game in games {
+        let bag = game.min_bag();
+        println!("{:>6} {:>5} {:>5} {:>5} {:>7}", game.id, bag.red, bag.green, bag.blue, bag.power());
+    }
+    println!();
+    let global = global_bag(games);
+    let histograms = histograms(games);
+    println!("{:>6} {:>5}  histogram (count: draws)", "color", "max");
+    for (color, histogram) in Color::ALL.iter().zip(histograms.iter()) {
+        let bins: Vec<String> = histogram.iter().map(|(count, n)| format!("{}:{}", count, n)).collect();
+        println!("{:>6} {:>5}  {}", color.name(), global.get(*color), bins.join(" "));
+    }
+    println!();
+    println!("Smallest bag for every game: {}", global);
+}
+
+
+fn global_bag(games: &[Game]) -> Bag {  // Smallest bag that makes every game feasible
+    let mut global = Bag::default();
+    for game in games {
+        let bag = game.min_bag();
+        for color in Color::ALL {
+            global.set(color, max(global.get(color), bag.get(color)));
         }
     }
-    (r, g, b)
-}
\ No newline at end of file
+    global
+}
+
+
+fn histograms(games: &[Game]) -> [BTreeMap<u32, u32>; 3] {  // One per color, skips absent colors
+    let mut res: [BTreeMap<u32, u32>; 3] = Default::default();
+    for draw in games.iter().flat_map(|g| g.draws.iter()) {
+        for (color, histogram) in Color::ALL.iter().zip(res.iter_mut()) {
+            let count = draw.get(*color);
+            if count > 0 {
+                *histogram.entry(count).or_insert(0) += 1;
+            }
+        }
+    }
+    res
+}
+
+
+fn query_feasible(games: &[Game], bag: &Bag) -> (Vec<u32>, Vec<Violation>) {
+    let mut feasible = vec![];
+    let mut violations = vec![];
+    for game in games {
+        let mut game_violations = game.violations(bag);
+        if game_violations.is_empty() {
+            feasible.push(game.id);
+        }
+        violations.append(&mut game_violations);
+    }
+    (feasible, violations)
+}
+
+
+fn parse_game(line: &str) -> Result<Game, String> {
+    let (header, plays) = line.split_once(": ").ok_or("missing ': '")?;
+    let id = header.strip_prefix("Game ").ok_or(format!("invalid header '{}'", header))?
+        .parse::<u32>().map_err(|e| format!("invalid game id '{}': {}", header, e))?;
+    let draws = plays.split("; ").map(count_play).collect::<Result<Vec<Bag>, String>>()?;
+    Ok(Game { id, draws })
+}
+
+
+fn count_play(play: &str) -> Result<Bag, String> {
+    let mut bag = Bag::default();
+    for ball in play.split(", ") {
+        let (num, color) = ball.split_once(' ').ok_or(format!("expected count and color, got '{}'", ball))?;
+        let num = num.parse::<u32>().map_err(|e| format!("invalid count '{}': {}", num, e))?;
+        bag.set(color.parse()?, num);
+    }
+    Ok(bag)
+}
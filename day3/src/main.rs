@@ -1,7 +1,7 @@
 use std::fs::File;
 use std::io::{self, prelude::*, BufReader};
 use std::vec;
-use clap::Parser;
+use clap::{Parser, ValueEnum};
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None, help_template = "\
@@ -15,101 +15,168 @@ struct Args {
     /// Input file
     #[arg(short, long = "input-path")]
     input_path: String,
+
+    /// Print every number and symbol with its adjacencies
+    #[arg(short, long)]
+    graph: bool,
+
+    /// Symbols that count as gears
+    #[arg(long, default_value = "*")]
+    gear_symbols: String,
+
+    /// Number of adjacent part numbers a gear needs
+    #[arg(long, default_value_t = 2)]
+    gear_neighbors: usize,
+
+    /// How the numbers around a gear are combined
+    #[arg(long, value_enum, default_value_t = Aggregate::Product)]
+    aggregate: Aggregate,
 }
 
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum Aggregate {
+    Product, Sum
+}
 
-fn main() -> io::Result<()>{
-    let args = Args::parse();
-    let file = File::open(args.input_path)?;
-    let reader = BufReader::new(file);
+#[derive(Debug)]
+struct GearRule {
+    symbols: String,
+    neighbors: usize,
+    aggregate: Aggregate,
+}
 
-    let mut board: Vec<Vec<char>> = vec![];
-    for line in reader.lines() {
-        insert(&mut board, &line?);
-    }
-    let sum = add_part_numbers(&board);
-    println!("{}", sum);
-    Ok(())
+#[derive(Debug)]
+struct Number {
+    value: u32,
+    row: usize,
+    start: usize,  // Inclusive column span
+    end: usize,
 }
 
+#[derive(Debug)]
+struct Symbol {
+    ch: char,
+    row: usize,
+    col: usize,
+}
 
-fn insert(board: &mut Vec<Vec<char>>, line: &str){
-    let row = board.len();
-    board.push(vec![]);
-    for char in line.chars() {
-        board[row].push(char);
-    }
+#[derive(Debug)]
+struct Schematic {
+    numbers: Vec<Number>,
+    symbols: Vec<Symbol>,
+    num_to_sym: Vec<Vec<usize>>,  // Bipartite graph stored from both sides
+    sym_to_num: Vec<Vec<usize>>,
 }
 
 
-fn add_part_numbers(board: &Vec<Vec<char>>) -> u32{
-    let mut gears: Vec<(usize, usize, u32)> = vec![];  // num, row, col of gear
-    for (row_num, row) in board.iter().enumerate() {
-        let mut curr_num = String::from("");
-        let mut start_col = 0;
-        for (i, value) in row.iter().enumerate() {
-            if value.is_ascii_digit() {
-                if curr_num.len() == 0 {
-                    start_col = i;
-                }
-                curr_num.push(*value);
-                if i == row.len()-1 {  // The case of a number right at the end of line
-                    let (is_gear, row, col) = check_gear(board, row_num, start_col, i);
-                    if is_gear {
-                        gears.push((row, col, curr_num.parse::<u32>().unwrap()));
+impl Schematic {
+    fn new(board: &[Vec<char>]) -> Self {
+        let mut numbers = vec![];
+        let mut symbols = vec![];
+        let mut sym_at: Vec<Vec<Option<usize>>> = board.iter().map(|row| vec![None; row.len()]).collect();
+        for (row_num, row) in board.iter().enumerate() {
+            let mut col = 0;
+            while col < row.len() {
+                if row[col].is_ascii_digit() {
+                    let start = col;
+                    while col < row.len() && row[col].is_ascii_digit() {
+                        col += 1;
                     }
+                    let value = row[start..col].iter().collect::<String>().parse::<u32>().unwrap();
+                    numbers.push(Number { value, row: row_num, start, end: col - 1 });
+                    continue;
                 }
-            } else if curr_num.len() > 0 {
-                let (is_gear, row, col) = check_gear(board, row_num, start_col, i-1);
-                if is_gear{
-                    gears.push((row, col, curr_num.parse::<u32>().unwrap()));
+                if row[col] != '.' {
+                    sym_at[row_num][col] = Some(symbols.len());
+                    symbols.push(Symbol { ch: row[col], row: row_num, col });
                 }
-                curr_num = String::from("");
+                col += 1;
             }
         }
-    }
-    gears.sort_unstable();
-    let mut num_equal = 0;
-    let mut sum_ratios = 0;
-    for i in 1..gears.len() {
-        if gears[i-1].0 == gears[i].0 && gears[i-1].1 == gears[i].1 {
-            num_equal += 1;
-            if i == gears.len()-1 && num_equal == 1 {
-                sum_ratios += gears[i].2 * gears[i-1].2;
-            }
-        } else  {
-            if num_equal == 1 {
-                sum_ratios += gears[i-1].2 * gears[i-2].2;
+
+        let mut num_to_sym = vec![vec![]; numbers.len()];
+        let mut sym_to_num = vec![vec![]; symbols.len()];
+        for (n, number) in numbers.iter().enumerate() {
+            for i in number.row.saturating_sub(1)..number.row + 2 {
+                for j in number.start.saturating_sub(1)..number.end + 2 {
+                    if let Some(s) = sym_at.get(i).and_then(|r| r.get(j)).copied().flatten() {
+                        num_to_sym[n].push(s);
+                        sym_to_num[s].push(n);
+                    }
+                }
             }
-            num_equal = 0;
         }
+        Schematic { numbers, symbols, num_to_sym, sym_to_num }
+    }
+
+    fn symbols_of(&self, number: usize) -> &[usize] {
+        &self.num_to_sym[number]
+    }
+
+    fn numbers_of(&self, symbol: usize) -> &[usize] {
+        &self.sym_to_num[symbol]
     }
-    return sum_ratios;
 }
 
-fn check_gear(board: &Vec<Vec<char>>, row_num: usize, start_col: usize, end_col: usize) -> (bool, usize, usize) {
-    let mut l_row = 0;
-    if row_num > 0 {
-        l_row = row_num - 1;
+
+fn main() -> io::Result<()>{
+    let args = Args::parse();
+    let file = File::open(args.input_path)?;
+    let reader = BufReader::new(file);
+
+    let mut board: Vec<Vec<char>> = vec![];
+    for line in reader.lines() {
+        board.push(line?.chars().collect());
     }
-    let mut l_col = 0;
-    if start_col > 0 {
-        l_col = start_col - 1;
+    let schematic = Schematic::new(&board);
+    if args.graph {
+        print_graph(&schematic);
     }
-    for i in l_row..row_num+2 {
-        for j in l_col..end_col+2 {
-            if i == row_num && j != l_col && j != end_col + 1 {
-                continue;
-            }
-            if is_valid(board, i, j) && board[i][j] == '*' {
-                return (true, i, j);
-            }
+    let rule = GearRule { symbols: args.gear_symbols, neighbors: args.gear_neighbors, aggregate: args.aggregate };
+    println!("{}", sum_part_numbers(&schematic));
+    println!("{}", sum_gear_ratios(&schematic, &rule));
+    Ok(())
+}
+
+
+fn print_graph(schematic: &Schematic) {
+    for (n, number) in schematic.numbers.iter().enumerate() {
+        let adj: Vec<String> = schematic.symbols_of(n).iter().map(|&s| {
+            let symbol = &schematic.symbols[s];
+            format!("'{}' ({}, {})", symbol.ch, symbol.row, symbol.col)
+        }).collect();
+        println!("{} at row {} cols {}..={}: {}", number.value, number.row, number.start, number.end, adj.join(", "));
+    }
+    for (s, symbol) in schematic.symbols.iter().enumerate() {
+        let adj: Vec<String> = schematic.numbers_of(s).iter().map(|&n| schematic.numbers[n].value.to_string()).collect();
+        println!("'{}' at ({}, {}): {}", symbol.ch, symbol.row, symbol.col, adj.join(", "));
+    }
+}
+
+
+fn sum_part_numbers(schematic: &Schematic) -> u32 {
+    let mut sum = 0;
+    for (n, number) in schematic.numbers.iter().enumerate() {
+        if !schematic.symbols_of(n).is_empty() {
+            sum += number.value;
         }
     }
-    (false, 0, 0)
+    sum
 }
 
 
-fn is_valid(board: &Vec<Vec<char>>, i: usize, j: usize) -> bool {
-    return i < board.len() && j < board[0].len();
-}
\ No newline at end of file
+fn sum_gear_ratios(schematic: &Schematic, rule: &GearRule) -> u64 {
+    let mut sum_ratios = 0;
+    for (s, symbol) in schematic.symbols.iter().enumerate() {
+        let adj = schematic.numbers_of(s);
+        if !rule.symbols.contains(symbol.ch) || adj.len() != rule.neighbors {
+            continue;
+        }
+        let values = adj.iter().map(|&n| schematic.numbers[n].value as u64);
+        sum_ratios += match rule.aggregate {
+            Aggregate::Product => values.product::<u64>(),
+            Aggregate::Sum => values.sum::<u64>(),
+        };
+    }
+    sum_ratios
+}
@@ -1,7 +1,8 @@
 use std::fs::File;
 use std::io::{self, prelude::*, BufReader};
 use clap::Parser;
-use std::collections::{HashSet, HashMap};
+use std::collections::HashSet;
+use std::fmt;
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None, help_template = "\
@@ -15,6 +16,65 @@ struct Args {
     /// Input file
     #[arg(short, long = "input-path")]
     input_path: String,
+
+    /// Print the number of copies of every card
+    #[arg(short, long)]
+    copies: bool,
+
+    /// Report anomalies in the cards before solving
+    #[arg(short, long)]
+    validate: bool,
+}
+
+#[derive(Debug)]
+struct Card {
+    id: u32,
+    winning: Vec<u32>,
+    owned: Vec<u32>,
+}
+
+#[derive(Debug)]
+enum Anomaly {
+    DuplicateWinning { card: u32, num: u32 },
+    DuplicateOwned { card: u32, num: u32 },
+    NonSequentialId { expected: u32, found: u32 },
+    CascadePastEnd { card: u32, matches: usize, last: u32 },
+}
+
+
+impl Card {
+    fn parse(line: &str) -> Result<Self, String> {
+        let (header, numbers) = line.split_once(':').ok_or("missing ':'")?;
+        let id = header.trim_start_matches("Card").trim().parse::<u32>()
+            .map_err(|e| format!("invalid card id '{}': {}", header, e))?;
+        let (winning, owned) = numbers.split_once('|').ok_or("missing '|'")?;
+        Ok(Card { id, winning: parse_numbers(winning)?, owned: parse_numbers(owned)? })
+    }
+
+    fn matches(&self) -> usize {
+        let owned: HashSet<&u32> = self.owned.iter().collect();
+        self.winning.iter().filter(|num| owned.contains(num)).count()
+    }
+
+    fn points(&self) -> Option<u64> {  // None if it does not fit in a u64
+        match self.matches() {
+            0 => Some(0),
+            m => 1u64.checked_shl(u32::try_from(m - 1).ok()?),
+        }
+    }
+}
+
+
+impl fmt::Display for Anomaly {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Anomaly::DuplicateWinning { card, num } => write!(f, "Card {}: winning number {} is repeated", card, num),
+            Anomaly::DuplicateOwned { card, num } => write!(f, "Card {}: owned number {} is repeated", card, num),
+            Anomaly::NonSequentialId { expected, found } => write!(f, "Card {}: expected card id {}", found, expected),
+            Anomaly::CascadePastEnd { card, matches, last } =>
+                write!(f, "Card {}: {} matches reach past the last card {}", card, matches, last),
+        }
+    }
 }
 
 
@@ -23,68 +83,96 @@ fn main() -> io::Result<()>{
     let file = File::open(args.input_path)?;
     let reader = BufReader::new(file);
 
-    let mut points = 0;
-    let mut card_count: HashMap<u32, u32> = HashMap::new();
+    let mut cards = vec![];
     for (i, line) in reader.lines().enumerate() {
-        let i = i as u32;
-        let matches = count_points(&line?);
-        if !card_count.contains_key(&i) {
-            card_count.insert(i, 1);
+        let card = Card::parse(&line?)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("line {}: {}", i + 1, e)))?;
+        cards.push(card);
+    }
+    if args.validate {
+        for anomaly in validate(&cards) {
+            println!("{}", anomaly);
         }
-        let num_cards = *card_count.get(&i).unwrap();
-        for k in i+1..i+matches+1 {
-            if !card_count.contains_key(&k) {
-                card_count.insert(k, 1);
-            }
-            if let Some(value) = card_count.get_mut(&k){
-                *value += num_cards;
-            }
+    }
+    let copies = count_copies(&cards).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    if args.copies {
+        for (card, num) in cards.iter().zip(copies.iter()) {
+            println!("Card {}: {}", card.id, num);
         }
     }
-    for (_, val) in card_count.iter() {
-        points += val;
+    let mut points: u64 = 0;
+    for card in cards.iter() {
+        points = card.points().and_then(|p| points.checked_add(p)).ok_or_else(|| io::Error::new(
+            io::ErrorKind::InvalidData, format!("Card {}: points overflow", card.id)))?;
     }
     println!("{}", points);
+    let total = copies.iter().try_fold(0u64, |acc, &c| acc.checked_add(c))
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "copies overflow"))?;
+    println!("{}", total);
     Ok(())
 }
 
-fn count_points(line: &str) -> u32 {
-    let mut win_nums: Vec<u32> = vec![];
-    let mut own_nums = HashSet::new();
-    read_input(line, &mut win_nums, &mut own_nums);
-    let matches = count_matches(&win_nums, &own_nums);
-    return matches;
-    // if matches == 0 {
-    //     return 0;
-    // }
-    // return 1 << (matches - 1);
-}
-
 
-fn count_matches(vec: &Vec<u32>, set: &HashSet<u32>) -> u32 {
-    let mut count = 0;
-    for num in vec {
-        if set.contains(num) {
-            count += 1;
+fn count_copies(cards: &[Card]) -> Result<Vec<u64>, String> {  // Difference array over the IDs, cascades skip missing IDs
+    let n = cards.len();
+    let mut order: Vec<usize> = (0..n).collect();
+    order.sort_by_key(|&i| cards[i].id);
+    let ids: Vec<u32> = order.iter().map(|&i| cards[i].id).collect();
+    let mut gained = vec![0u64; n + 1];  // Split in two so that every step is a checked u64 operation
+    let mut expired = vec![0u64; n + 1];
+    let mut copies = vec![0; n];
+    let mut won: u64 = 0;
+    for (pos, &i) in order.iter().enumerate() {
+        let card = &cards[i];
+        let overflow = || format!("Card {}: copies overflow", card.id);
+        won = won.checked_add(gained[pos]).ok_or_else(overflow)? - expired[pos];
+        copies[i] = won.checked_add(1).ok_or_else(overflow)?;
+        let last_won = card.id as u64 + card.matches() as u64;  // Cards N+1..=N+matches
+        let start = ids.partition_point(|&id| id <= card.id);
+        let end = ids.partition_point(|&id| id as u64 <= last_won);
+        if start < end {
+            gained[start] = gained[start].checked_add(copies[i]).ok_or_else(overflow)?;
+            expired[end] = expired[end].checked_add(copies[i]).ok_or_else(overflow)?;
         }
     }
-    return count;
+    Ok(copies)
 }
 
 
-fn read_input(line: &str, win_nums: &mut Vec<u32>, own_nums: &mut HashSet<u32>){
-    let split: Vec<&str> = line.split(':').collect();
-    let split2: Vec<&str> = split[1].split('|').collect();
-    let winning = split2[0];
-    let own = split2[1];
-    for num_str in winning.split(' ') {
-        if let Ok(num) = num_str.parse::<u32>(){
-            win_nums.push(num);
+fn validate(cards: &[Card]) -> Vec<Anomaly> {
+    let mut anomalies = vec![];
+    let last = cards.iter().map(|c| c.id).max().unwrap_or(0);
+    for (i, card) in cards.iter().enumerate() {
+        if let Some(prev) = i.checked_sub(1).map(|j| &cards[j]) {  // A single gap is reported once
+            let expected = prev.id.wrapping_add(1);
+            if card.id != expected {
+                anomalies.push(Anomaly::NonSequentialId { expected, found: card.id });
+            }
         }
-    }
-    for num_str in own.split(' ') {
-        if let Ok(num) = num_str.parse::<u32>(){
-            own_nums.insert(num);
+        for num in duplicates(&card.winning) {
+            anomalies.push(Anomaly::DuplicateWinning { card: card.id, num });
+        }
+        for num in duplicates(&card.owned) {
+            anomalies.push(Anomaly::DuplicateOwned { card: card.id, num });
+        }
+        let matches = card.matches();
+        if card.id as u64 + matches as u64 > last as u64 {
+            anomalies.push(Anomaly::CascadePastEnd { card: card.id, matches, last });
         }
     }
+    anomalies
+}
+
+
+fn duplicates(nums: &[u32]) -> Vec<u32> {  // Each repeated number is reported once
+    let mut seen = HashSet::new();
+    let mut reported = HashSet::new();
+    nums.iter().filter(|&&num| !seen.insert(num) && reported.insert(num)).copied().collect()
+}
+
+
+fn parse_numbers(s: &str) -> Result<Vec<u32>, String> {
+    s.split_whitespace()
+        .map(|num| num.parse::<u32>().map_err(|e| format!("invalid number '{}': {}", num, e)))
+        .collect()
 }
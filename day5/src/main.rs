@@ -1,6 +1,7 @@
 use std::fs::File;
 use std::io::{self, prelude::*, BufReader};
-use clap::Parser;
+use clap::{Parser, ValueEnum};
+use std::cmp::{max, min};
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None, help_template = "\
@@ -14,77 +15,313 @@ struct Args {
     /// Input file
     #[arg(short, long = "input-path")]
     input_path: String,
+
+    /// First category of the queried chain
+    #[arg(long, default_value = "seed")]
+    from: String,
+
+    /// Last category of the queried chain
+    #[arg(long, default_value = "location")]
+    to: String,
+
+    /// Values to push through the queried chain
+    #[arg(long)]
+    value: Vec<u64>,
+
+    /// Print the queried chain as a single piecewise-linear map
+    #[arg(long)]
+    print_map: bool,
+
+    /// Print the ranges of the first category that end up below this value
+    #[arg(long)]
+    below: Option<u64>,
+
+    /// Recompute the minimum location going backwards from the locations
+    #[arg(long)]
+    verify: bool,
+
+    /// How the numbers in the seeds line are read
+    #[arg(long, value_enum, default_value_t = SeedMode::Ranges)]
+    seeds: SeedMode,
+
+    /// Print every location range reached by the seeds, not only the minimum
+    #[arg(long)]
+    print_ranges: bool,
 }
 
-// https://github.com/CantTouchDis/AoC2023/blob/master/day-05/src/bin/part2.rs
-fn main() -> io::Result<()>{
-    let args = Args::parse();
-    let file = File::open(args.input_path)?;
-    let reader = BufReader::new(file);
-    let mut lines = reader.lines().map(|x| x.unwrap());
-    let seeds : Vec<u64> = lines.next().unwrap().split_at(6).1.split_ascii_whitespace().map(|s| s.parse::<u64>().unwrap()).collect();
-    let mut i = 0;
-    let mut ranges = vec![];
-    while i < seeds.len() {
-        ranges.push((seeds[i], seeds[i]+seeds[i+1]));
-        i += 2;
-    }
-    // skip empty line
-    lines.next();
-    let mut previous : Vec<(u64, u64)> = ranges.clone();
-    let mut current : Vec<(u64, u64)> = vec![];
-    for l in lines {
-        if l.is_empty() {
-
-        }
-        else if !l.chars().nth(0).unwrap().is_digit(10) {
-            // start a new map
-            previous.append(&mut current);
-            current = vec![];
-            //println!("At start of {} we got {:?}", l, previous);
-        }
-        else {
-            let m = l.split_ascii_whitespace().map(|a| a.parse::<u64>().unwrap() ).collect::<Vec<u64>>();
-            let mut new_previous: Vec<(u64, u64)> = vec![];
-
-            // aliases to keep my sanity
-            let (dst, src_start, src_end, map_length) = (m[0], m[1], m[1] + m[2], m[2]);
-
-            for (b, e) in &previous {
-                // end before begin or begin after end
-                if *e <= src_start || *b >= src_end {
-                    new_previous.push((*b, *e));
-                    continue;
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum SeedMode {
+    Values,  // Each number is a seed
+    Ranges,  // Pairs of start and length
+}
+
+type Range = (u64, u64);  // Half open [start, end)
+
+#[derive(Clone, Copy, Debug)]
+struct Segment {
+    start: u64,  // Maps [start, end) to [start + offset, end + offset)
+    end: u64,
+    offset: i64,
+}
+
+#[derive(Clone, Debug)]
+struct RangeMap {  // Segments are sorted and cover the whole u64 domain
+    from: String,
+    to: String,
+    segments: Vec<Segment>,
+}
+
+#[derive(Debug)]
+struct Almanac {
+    seeds: Vec<u64>,
+    maps: Vec<RangeMap>,
+}
+
+
+impl Segment {
+    fn shift(x: u64, offset: i64) -> u64 {
+        x.checked_add_signed(offset).expect("mapped value out of range")
+    }
+}
+
+
+impl RangeMap {
+    fn identity(name: &str) -> Self {
+        let segments = vec![Segment { start: 0, end: u64::MAX, offset: 0 }];
+        RangeMap { from: String::from(name), to: String::from(name), segments }
+    }
+
+    fn new(from: &str, to: &str, mut entries: Vec<(u64, u64, u64)>) -> Result<Self, String> {  // (dst, src, len)
+        entries.sort_unstable_by_key(|e| e.1);
+        let mut segments = vec![];
+        let mut pos = 0;
+        for (dst, src, len) in entries {
+            if src < pos {
+                return Err(format!("{}-to-{} map: range starting at {} overlaps the previous one", from, to, src));
+            }
+            if src > pos {
+                segments.push(Segment { start: pos, end: src, offset: 0 });
+            }
+            let end = src.checked_add(len).filter(|_| dst.checked_add(len).is_some())
+                .ok_or(format!("{}-to-{} map: range {} {} {} goes past {}", from, to, dst, src, len, u64::MAX))?;
+            let offset = i64::try_from(dst as i128 - src as i128)
+                .map_err(|_| format!("{}-to-{} map: range {} {} {} moves values too far", from, to, dst, src, len))?;
+            segments.push(Segment { start: src, end, offset });
+            pos = end;
+        }
+        if pos < u64::MAX {
+            segments.push(Segment { start: pos, end: u64::MAX, offset: 0 });
+        }
+        Ok(RangeMap { from: String::from(from), to: String::from(to), segments })
+    }
+
+    fn apply(&self, x: u64) -> Option<u64> {  // None outside of the domain, i.e. for u64::MAX
+        let i = self.segments.partition_point(|s| s.end <= x);
+        self.segments.get(i).map(|seg| Segment::shift(x, seg.offset))
+    }
+
+    fn map_ranges(&self, ranges: &[Range]) -> Vec<Range> {
+        let mut res = vec![];
+        for &(b, e) in ranges {
+            let first = self.segments.partition_point(|s| s.end <= b);
+            for seg in self.segments[first..].iter().take_while(|s| s.start < e) {
+                let (l, r) = (max(b, seg.start), min(e, seg.end));
+                res.push((Segment::shift(l, seg.offset), Segment::shift(r, seg.offset)));
+            }
+        }
+        merge_ranges(res)
+    }
+
+    fn preimage(&self, ranges: &[Range]) -> Vec<Range> {  // Maps can be many-to-one, so no inverse map
+        let mut res = vec![];
+        for seg in self.segments.iter() {
+            let (b, e) = (Segment::shift(seg.start, seg.offset), Segment::shift(seg.end, seg.offset));
+            for &(l, r) in ranges {
+                let (l, r) = (max(l, b), min(r, e));
+                if l < r {
+                    res.push((Segment::shift(l, -seg.offset), Segment::shift(r, -seg.offset)));
                 }
-                // full overlap
-                if *b >= src_start && *e <= src_end {
-                    current.push((dst + *b - src_start, dst + *e - src_start));
+            }
+        }
+        merge_ranges(res)
+    }
+
+    fn then(&self, next: &RangeMap) -> RangeMap {  // Composition: next applied after self
+        let mut segments: Vec<Segment> = vec![];
+        for seg in self.segments.iter() {
+            let (b, e) = (Segment::shift(seg.start, seg.offset), Segment::shift(seg.end, seg.offset));
+            let first = next.segments.partition_point(|s| s.end <= b);
+            for other in next.segments[first..].iter().take_while(|s| s.start < e) {
+                let start = Segment::shift(max(b, other.start), -seg.offset);
+                let end = Segment::shift(min(e, other.end), -seg.offset);
+                let offset = seg.offset + other.offset;
+                match segments.last_mut() {
+                    Some(last) if last.end == start && last.offset == offset => last.end = end,
+                    _ => segments.push(Segment { start, end, offset }),
                 }
-                // fully contained
-                else if *b < src_start && *e > src_end {
-                    new_previous.push((*b, src_start));
-                    new_previous.push((src_end, *e));
-                    current.push((dst, dst + map_length));
+            }
+        }
+        RangeMap { from: self.from.clone(), to: next.to.clone(), segments }
+    }
+}
+
+
+impl Almanac {
+    fn parse(mut lines: impl Iterator<Item = String>) -> Result<Self, String> {
+        let first = lines.next().ok_or("empty almanac")?;
+        let seeds = parse_numbers(first.strip_prefix("seeds:").ok_or("missing seeds line")?)?;
+        let mut maps = vec![];
+        let mut header: Option<(String, String)> = None;
+        let mut entries = vec![];
+        for line in lines.chain(std::iter::once(String::new())) {
+            if line.is_empty() {
+                if let Some((from, to)) = header.take() {
+                    maps.push(RangeMap::new(&from, &to, entries)?);
+                    entries = vec![];
                 }
-                // begin before begin
-                else if *b < src_start {
-                    new_previous.push((*b, src_start));
-                    current.push((dst, dst + *e - src_start));
+            } else if let Some(name) = line.strip_suffix(" map:") {
+                let (from, to) = name.split_once("-to-").ok_or(format!("invalid map name '{}'", name))?;
+                header = Some((String::from(from), String::from(to)));
+            } else {
+                if header.is_none() {
+                    return Err(format!("range '{}' outside of a map", line));
                 }
-                else if *e > src_end {
-                    new_previous.push((src_end, *e));
-                    current.push((dst + *b - src_start, dst + map_length));
+                match parse_numbers(&line)?[..] {
+                    [dst, src, len] => entries.push((dst, src, len)),
+                    _ => return Err(format!("expected three numbers, got '{}'", line)),
                 }
-                else {
-                    println!("{} {} {} unhandled for range ({}, {})", m[0], m[1], m[2], *b, *e);
+            }
+        }
+        Ok(Almanac { seeds, maps })
+    }
+
+    fn seed_ranges(&self, mode: SeedMode) -> Result<Vec<Range>, String> {
+        let ranges = match mode {
+            SeedMode::Values => self.seeds.iter()
+                .map(|&s| s.checked_add(1).map(|end| (s, end)).ok_or(format!("seed {} is out of range", s)))
+                .collect::<Result<_, String>>()?,
+            SeedMode::Ranges => {
+                if !self.seeds.len().is_multiple_of(2) {
+                    return Err(format!("{} seed numbers can not be read as pairs", self.seeds.len()));
                 }
+                self.seeds.chunks(2)
+                    .map(|c| c[0].checked_add(c[1]).map(|end| (c[0], end))
+                        .ok_or(format!("seed range {} {} goes past {}", c[0], c[1], u64::MAX)))
+                    .collect::<Result<_, String>>()?
+            }
+        };
+        Ok(merge_ranges(ranges))
+    }
+
+    fn min_location_backwards(&self, seeds: &[Range]) -> Result<Option<u64>, String> {
+        let chain = self.chain("seed", "location")?;
+        let reaches = |x: u64| !intersect_ranges(&chain.preimage(&[(0, x + 1)]), seeds).is_empty();
+        let (mut lo, mut hi) = (0, u64::MAX - 1);
+        if !reaches(hi) {
+            return Ok(None);
+        }
+        while lo < hi {  // Smallest location whose preimage contains a seed
+            let mid = lo + (hi - lo) / 2;
+            if reaches(mid) { hi = mid; } else { lo = mid + 1; }
+        }
+        Ok(Some(lo))
+    }
+
+    fn chain(&self, from: &str, to: &str) -> Result<RangeMap, String> {
+        let mut res = RangeMap::identity(from);
+        for _ in 0..=self.maps.len() {  // Longer chains would be going in circles
+            if res.to == to {
+                return Ok(res);
             }
-            previous = new_previous;
+            let next = self.maps.iter().find(|m| m.from == res.to)
+                .ok_or(format!("no map goes from {} towards {}", res.to, to))?;
+            res = res.then(next);
+        }
+        Err(format!("no chain from {} to {}", from, to))
+    }
+}
+
+
+fn main() -> io::Result<()>{
+    let args = Args::parse();
+    let file = File::open(args.input_path)?;
+    let reader = BufReader::new(file);
+    let lines = reader.lines().collect::<io::Result<Vec<String>>>()?;
+    let almanac = Almanac::parse(lines.into_iter()).map_err(invalid)?;
+
+    let seeds = almanac.seed_ranges(args.seeds).map_err(invalid)?;
+    let chain = almanac.chain(&args.from, &args.to).map_err(invalid)?;
+    if args.print_map {
+        for seg in chain.segments.iter() {
+            println!("[{}, {}) {:+}", seg.start, seg.end, seg.offset);
+        }
+    }
+    for v in args.value {
+        let mapped = chain.apply(v).ok_or_else(|| invalid(format!("value {} is out of range", v)))?;
+        println!("{} {} -> {} {}", chain.from, v, chain.to, mapped);
+    }
+    if let Some(limit) = args.below {
+        let mut ranges = chain.preimage(&[(0, limit)]);
+        if chain.from == "seed" {  // Only seeds are known upfront
+            ranges = intersect_ranges(&ranges, &seeds);
+        }
+        for (b, e) in ranges {
+            println!("{} [{}, {}) -> {} below {}", chain.from, b, e, chain.to, limit);
+        }
+    }
+
+    let locations = almanac.chain("seed", "location").map_err(invalid)?.map_ranges(&seeds);
+    if args.print_ranges {
+        for (b, e) in locations.iter() {
+            println!("location [{}, {})", b, e);
         }
     }
-    previous.append(&mut current);
-    // println!("We got {:?} in the end", previous);
-    let res = previous.iter().map(|(a, _)| a).reduce(|a, b| std::cmp::min(a, b)).unwrap().to_string();
-    println!("{}", res);
+    let min_location = locations.first().ok_or_else(|| invalid(String::from("no seeds")))?.0;
+    if args.verify {
+        let backwards = almanac.min_location_backwards(&seeds).map_err(invalid)?;
+        if backwards != Some(min_location) {
+            return Err(invalid(format!("going backwards gives {:?}, forwards {}", backwards, min_location)));
+        }
+    }
+    println!("{}", min_location);
     Ok(())
 }
+
+
+fn invalid(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+
+fn merge_ranges(mut ranges: Vec<Range>) -> Vec<Range> {  // Sorted, disjoint and non-empty
+    ranges.sort_unstable();
+    let mut res: Vec<Range> = vec![];
+    for (b, e) in ranges.into_iter().filter(|(b, e)| b < e) {
+        match res.last_mut() {
+            Some(last) if b <= last.1 => last.1 = max(last.1, e),
+            _ => res.push((b, e)),
+        }
+    }
+    res
+}
+
+
+fn intersect_ranges(a: &[Range], b: &[Range]) -> Vec<Range> {  // Both sorted and disjoint
+    let (mut i, mut j) = (0, 0);
+    let mut res = vec![];
+    while i < a.len() && j < b.len() {
+        let (l, r) = (max(a[i].0, b[j].0), min(a[i].1, b[j].1));
+        if l < r {
+            res.push((l, r));
+        }
+        if a[i].1 < b[j].1 { i += 1; } else { j += 1; }
+    }
+    res
+}
+
+
+fn parse_numbers(s: &str) -> Result<Vec<u64>, String> {
+    s.split_ascii_whitespace()
+        .map(|num| num.parse::<u64>().map_err(|e| format!("invalid number '{}': {}", num, e)))
+        .collect()
+}
@@ -1,6 +1,6 @@
 use std::fs::File;
 use std::io::{self, prelude::*, BufReader};
-use clap::Parser;
+use clap::{Parser, ValueEnum};
 use itertools::izip;
 
 #[derive(Parser, Debug)]
@@ -15,6 +15,16 @@ struct Args {
     /// Input file
     #[arg(short, long = "input-path")]
     input_path: String,
+
+    /// How the numbers of each line are read
+    #[arg(short, long, value_enum, default_value_t = Kerning::Separate)]
+    kerning: Kerning,
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum Kerning {
+    Separate,  // One race per number
+    Joined,    // Spaces are ignored, a single race per line
 }
 
 
@@ -22,32 +32,115 @@ fn main() -> io::Result<()>{
     let args = Args::parse();
     let file = File::open(args.input_path)?;
     let reader = BufReader::new(file);
-    let mut lines = reader.lines().map(|x| x.unwrap());
-    let times = lines
-        .next().unwrap()
-        .split(':')
-        .nth(1).unwrap()
-        .split_whitespace()
-        .map(|x| x.parse::<u64>().unwrap())
-        .collect::<Vec<u64>>();
-    let dists = lines
-        .next().unwrap()
-        .split(':')
-        .nth(1).unwrap()
-        .split_whitespace()
-        .map(|x| x.parse::<u64>().unwrap())
-        .collect::<Vec<u64>>();
+    let mut lines = reader.lines();
+    let times = parse_line(&lines.next().ok_or_else(|| invalid(String::from("missing Time line")))??, args.kerning)
+        .map_err(invalid)?;
+    let dists = parse_line(&lines.next().ok_or_else(|| invalid(String::from("missing Distance line")))??, args.kerning)
+        .map_err(invalid)?;
+    if times.len() != dists.len() {
+        return Err(invalid(format!("{} times but {} distances", times.len(), dists.len())));
+    }
     let mut res = 1;
     for (t, d) in izip!(times, dists) {
-        res *= count_ways(t as f64, d as f64);
+        res *= count_ways(t, d);
     }
     println!("{}", res);
     Ok(())
 }
 
 
-fn count_ways(t: f64, d: f64) -> u64 {
-    let upper = ((t + (t * t - 4. * d).sqrt()) / 2. - 0.000001).floor() as u64;
-    let lower = ((t - (t * t - 4. * d).sqrt()) / 2. + 0.000001).ceil() as u64;
-    return upper - lower + 1;
-}
\ No newline at end of file
+fn invalid(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+
+fn parse_line(line: &str, kerning: Kerning) -> Result<Vec<u64>, String> {
+    let values = line.split_once(':').ok_or(format!("missing ':' in '{}'", line))?.1;
+    match kerning {
+        Kerning::Separate => values
+            .split_whitespace()
+            .map(|x| x.parse::<u64>().map_err(|e| format!("invalid number '{}': {}", x, e)))
+            .collect(),
+        Kerning::Joined => {
+            let mut num: u64 = 0;
+            for c in values.chars().filter(|c| !c.is_whitespace()) {
+                let digit = c.to_digit(10).ok_or(format!("invalid digit '{}'", c))?;
+                num = num.checked_mul(10).and_then(|n| n.checked_add(digit as u64))
+                    .ok_or(format!("'{}' does not fit in 64 bits", values.trim()))?;
+            }
+            Ok(vec![num])
+        }
+    }
+}
+
+
+fn count_ways(t: u64, d: u64) -> u64 {  // Holding h ms wins when h * (t - h) > d
+    let (t, d) = (t as u128, d as u128);
+    let beats = |h: u128| h * (t - h) > d;
+    if t * t < 4 * d {
+        return 0;
+    }
+    let mut lower = (t - (t * t - 4 * d).isqrt()) / 2;  // Within one of the smaller root
+    while lower > 0 && beats(lower - 1) {
+        lower -= 1;
+    }
+    while lower <= t / 2 && !beats(lower) {
+        lower += 1;
+    }
+    if lower > t / 2 {
+        return 0;
+    }
+    (t - 2 * lower + 1) as u64  // Symmetric around t / 2
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn brute_force(t: u64, d: u64) -> u64 {
+        (0..=t).filter(|h| h * (t - h) > d).count() as u64
+    }
+
+    #[test]
+    fn joined_overflow() {
+        assert_eq!(parse_line("Time:  7  15   30", Kerning::Joined), Ok(vec![71530]));
+        assert_eq!(parse_line("Time:  7  15   30", Kerning::Separate), Ok(vec![7, 15, 30]));
+        assert!(parse_line("Time: 18446744073 709551615", Kerning::Joined).is_ok());
+        assert!(parse_line("Time: 18446744073 709551616", Kerning::Joined).is_err());
+    }
+
+    #[test]
+    fn statement_examples() {
+        assert_eq!(count_ways(7, 9), 4);
+        assert_eq!(count_ways(15, 40), 8);
+        assert_eq!(count_ways(30, 200), 9);
+        assert_eq!(count_ways(71530, 940200), 71503);
+    }
+
+    #[test]
+    fn record_is_a_perfect_square() {
+        assert_eq!(count_ways(4, 4), 0);  // Tie at h = 2 does not beat the record
+        assert_eq!(count_ways(10, 25), 0);
+        assert_eq!(count_ways(10, 24), 1);
+        assert_eq!(count_ways(10, 21), 3);  // Ties at h = 3 and h = 7
+    }
+
+    #[test]
+    fn matches_brute_force() {
+        for t in 0..60 {
+            for d in 0..(t * t / 4 + 2) {
+                assert_eq!(count_ways(t, d), brute_force(t, d), "t = {}, d = {}", t, d);
+            }
+        }
+    }
+
+    #[test]
+    fn large_values() {
+        let t = 1 << 30;
+        let h = (1 << 29) - 12345;
+        assert_eq!(count_ways(t, h * (t - h)), 2 * 12345 - 1);
+        assert_eq!(count_ways(u64::MAX, 0), u64::MAX - 1);
+        assert_eq!(count_ways(45977295, 305106211101695), 29891250);
+    }
+}
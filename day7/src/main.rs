@@ -1,9 +1,7 @@
 use std::fs::File;
 use std::io::{self, prelude::*, BufReader};
-use clap::Parser;
+use clap::{Parser, ValueEnum};
 use std::collections::HashMap;
-use std::cmp::{Eq, PartialEq, Ord, PartialOrd};
-use core::cmp::Ordering;
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None, help_template = "\
@@ -17,238 +15,149 @@ struct Args {
     /// Input file
     #[arg(short, long = "input-path")]
     input_path: String,
-}
 
-#[derive(Debug)]
-#[derive(Eq, PartialEq)]
-struct Hand {
-    strength: u32, first: char, second: char, third: char, fourth: char, fifth: char,
-    bid: u32
-}
+    /// Use the standard rules instead of the joker ones: no wild card, J between T and Q
+    #[arg(long)]
+    no_joker: bool,
 
-fn cmp(a: char, b: char) -> Ordering {
-    if a == b {
-        return Ordering::Equal;
-    }
-    let map = HashMap::from([
-        ('A', 15), ('K', 14), ('Q', 13),
-        ('J', 2), ('T', 11), ('9', 10),
-        ('8', 9), ('7', 8), ('6', 7),
-        ('5', 6), ('4', 5), ('3', 4),
-        ('2', 3),
-    ]);
-    if map[&a] < map[&b] {
-        return Ordering::Less;
-    } else {
-        return Ordering::Greater;
-    }
+    /// Card order from weakest to strongest, overrides the default of the rules
+    #[arg(short, long)]
+    order: Option<String>,
+
+    /// Wild card, overrides the default of the rules
+    #[arg(short, long)]
+    wild: Option<char>,
+
+    /// Number of cards in a hand
+    #[arg(long, default_value_t = 5)]
+    hand_size: usize,
+
+    /// Print every hand in rank order with its winnings
+    #[arg(short, long, value_enum)]
+    report: Option<ReportFormat>,
 }
 
-impl Ord for Hand {
-    fn cmp(&self, other: &Self) -> Ordering {
-        if self.strength < other.strength {
-            return Ordering::Less;
-        }
-        if self.strength > other.strength {
-            return Ordering::Greater;
-        }
-        if cmp(self.first, other.first) != Ordering::Equal {
-            return cmp(self.first, other.first);
-        }
-        if cmp(self.second, other.second) != Ordering::Equal {
-            return cmp(self.second, other.second);
-        }
-        if cmp(self.third, other.third) != Ordering::Equal {
-            return cmp(self.third, other.third);
-        }
-        if cmp(self.fourth, other.fourth) != Ordering::Equal {
-            return cmp(self.fourth, other.fourth);
-        }
-        if cmp(self.fifth, other.fifth) != Ordering::Equal {
-            return cmp(self.fifth, other.fifth);
-        }
-        Ordering::Equal
-    }
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum ReportFormat {
+    Table, Csv
 }
 
-impl PartialOrd for Hand {
-    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        Some(self.cmp(other))
-    }
+#[derive(Debug)]
+struct Rules {
+    order: String,  // Weakest to strongest
+    wild: Option<char>,
+    hand_size: usize,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum HandType {
+    HighCard, OnePair, TwoPair, ThreeOfAKind, FullHouse, FourOfAKind, FiveOfAKind
+}
 
-impl Hand {
-    fn new(line: &str, bid: u32) -> Self{
-        let first: char = line.chars().nth(0).unwrap();
-        let second: char = line.chars().nth(1).unwrap();
-        let third: char = line.chars().nth(2).unwrap();
-        let fourth: char = line.chars().nth(3).unwrap();
-        let fifth: char = line.chars().nth(4).unwrap();
-        let mut hand  = Hand{
-            first, second, third, fourth, fifth, 
-            strength: 0, bid
-        }; 
-        hand.compute_strength();
-        return hand;
-    }
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+struct Hand {  // Field order matters for the derived Ord
+    kind: HandType,
+    ranks: Vec<usize>,
+    cards: Vec<char>,
+    bid: u32,
+}
 
 
-    fn compute_strength(&mut self) {
-        if self.five_equal() {
-            self.strength = 6; return;
-        }
-        if self.four_equal() {
-            self.strength = 5; return;
-        }
-        if self.three_equal() {
-            if self.count_pairs() == 2 {
-                self.strength = 4; return;
-            } else {
-                self.strength = 3; return;
+impl Rules {
+    fn new(args: &Args) -> Result<Self, String> {
+        let (order, wild) = match args.no_joker {
+            false => ("J23456789TQKA", Some('J')),
+            true => ("23456789TJQKA", None),
+        };
+        let rules = Rules {
+            order: args.order.clone().unwrap_or(String::from(order)),
+            wild: args.wild.or(wild),
+            hand_size: args.hand_size,
+        };
+        for (i, c) in rules.order.chars().enumerate() {
+            if rules.order.chars().skip(i + 1).any(|other| other == c) {
+                return Err(format!("card '{}' is repeated in the order", c));
             }
         }
-        if self.count_pairs() == 2 {
-            self.strength = 2; return;
-        }
-        if self.count_pairs() == 1 {
-            self.strength = 1; return;
+        if let Some(w) = rules.wild.filter(|&w| rules.rank(w).is_none()) {
+            return Err(format!("wild card '{}' is not in the order", w));
         }
-        self.strength = 0; return;
+        Ok(rules)
     }
 
-
-    fn five_equal(&self) -> bool {
-        let mut freqs = HashMap::from(
-            [
-                ('A', 0), ('K', 0), ('Q', 0),
-                ('J', 0), ('T', 0), ('9', 0),
-                ('8', 0), ('7', 0), ('6', 0),
-                ('5', 0), ('4', 0), ('3', 0),
-                ('2', 0),
-            ]
-        );
-        *freqs.entry(self.first).or_insert(0) += 1;
-        *freqs.entry(self.second).or_insert(0) += 1;
-        *freqs.entry(self.third).or_insert(0) += 1;
-        *freqs.entry(self.fourth).or_insert(0) += 1;
-        *freqs.entry(self.fifth).or_insert(0) += 1;
-        for (k, v) in &freqs {
-            if v + freqs[&'J'] == 5 && *k != 'J' {
-                return true;
-            }
-            if *v == 5 {
-                return true;
-            }
-        }
-        false
+    fn rank(&self, card: char) -> Option<usize> {
+        self.order.chars().position(|c| c == card)
     }
+}
 
 
-    fn four_equal(&self) -> bool {
-        let mut freqs = HashMap::from(
-            [
-                ('A', 0), ('K', 0), ('Q', 0),
-                ('J', 0), ('T', 0), ('9', 0),
-                ('8', 0), ('7', 0), ('6', 0),
-                ('5', 0), ('4', 0), ('3', 0),
-                ('2', 0),
-            ]
-        );
-        *freqs.entry(self.first).or_insert(0) += 1;
-        *freqs.entry(self.second).or_insert(0) += 1;
-        *freqs.entry(self.third).or_insert(0) += 1;
-        *freqs.entry(self.fourth).or_insert(0) += 1;
-        *freqs.entry(self.fifth).or_insert(0) += 1;
-        for (k, v) in &freqs {
-            if v + freqs[&'J'] == 4 && *k != 'J' {
-                return true;
-            }
-            if *v == 4 {
-                return true;
-            }
+impl HandType {
+    fn classify(cards: &[char], rules: &Rules) -> Self {  // Groups of five or more count as five of a kind
+        let mut freqs = HashMap::<char, usize>::new();
+        for &card in cards.iter().filter(|&&c| Some(c) != rules.wild) {
+            *freqs.entry(card).or_insert(0) += 1;
+        }
+        let mut counts: Vec<usize> = freqs.into_values().collect();
+        counts.sort_unstable_by(|a, b| b.cmp(a));
+        let jokers = cards.len() - counts.iter().sum::<usize>();
+        match counts.first_mut() {
+            Some(largest) => *largest += jokers,
+            None => counts.push(jokers),
+        }
+        match (counts[0], counts.get(1).copied().unwrap_or(0)) {
+            (5.., _) => HandType::FiveOfAKind,
+            (4, _) => HandType::FourOfAKind,
+            (3, 2..) => HandType::FullHouse,
+            (3, _) => HandType::ThreeOfAKind,
+            (2, 2) => HandType::TwoPair,
+            (2, _) => HandType::OnePair,
+            _ => HandType::HighCard,
         }
-        false
     }
+}
 
 
-    fn three_equal(&self) -> bool {
-        let mut freqs = HashMap::from(
-            [
-                ('A', 0), ('K', 0), ('Q', 0),
-                ('J', 0), ('T', 0), ('9', 0),
-                ('8', 0), ('7', 0), ('6', 0),
-                ('5', 0), ('4', 0), ('3', 0),
-                ('2', 0),
-            ]
-        );
-        *freqs.entry(self.first).or_insert(0) += 1;
-        *freqs.entry(self.second).or_insert(0) += 1;
-        *freqs.entry(self.third).or_insert(0) += 1;
-        *freqs.entry(self.fourth).or_insert(0) += 1;
-        *freqs.entry(self.fifth).or_insert(0) += 1;
-        for (k, v) in &freqs {
-            if v + freqs[&'J'] == 3 && *k != 'J' {
-                return true;
-            }
-            if *v == 3 {
-                return true;
-            }
-        }
-        false
+impl Hand {
+    fn new(line: &str, rules: &Rules) -> Result<Self, String> {
+        let (cards, bid) = line.split_once(' ').ok_or(format!("missing bid in '{}'", line))?;
+        let bid = bid.parse::<u32>().map_err(|e| format!("invalid bid '{}': {}", bid, e))?;
+        let cards: Vec<char> = cards.chars().collect();
+        if cards.len() != rules.hand_size {
+            return Err(format!("hand '{}' does not have {} cards", line, rules.hand_size));
+        }
+        let ranks = cards.iter()
+            .map(|&c| rules.rank(c).ok_or(format!("unknown card '{}'", c)))
+            .collect::<Result<Vec<usize>, String>>()?;
+        Ok(Hand { kind: HandType::classify(&cards, rules), ranks, cards, bid })
     }
 
-
-    fn count_pairs(&self) -> u32 {
-        let mut freqs = HashMap::from(
-            [
-                ('A', 0), ('K', 0), ('Q', 0),
-                ('J', 0), ('T', 0), ('9', 0),
-                ('8', 0), ('7', 0), ('6', 0),
-                ('5', 0), ('4', 0), ('3', 0),
-                ('2', 0),
-            ]
-        );
-        *freqs.entry(self.first).or_insert(0) += 1;
-        *freqs.entry(self.second).or_insert(0) += 1;
-        *freqs.entry(self.third).or_insert(0) += 1;
-        *freqs.entry(self.fourth).or_insert(0) += 1;
-        *freqs.entry(self.fifth).or_insert(0) += 1;
-        let mut pairs = 0;
-        for (k, v) in &freqs {
-            if *v >= 2 && *k != 'J' {
-                pairs += 1;
-            }
+    fn effective(&self, rules: &Rules) -> String {  // Wild cards become the best card of the largest group
+        let mut freqs = HashMap::<char, usize>::new();
+        for &card in self.cards.iter().filter(|&&c| Some(c) != rules.wild) {
+            *freqs.entry(card).or_insert(0) += 1;
         }
-        if pairs == 0 {
-            for (k, v) in &freqs {
-                if *v > 0 && *k == 'J' {
-                    pairs = 1;
-                }
-            }
-        }
-        return pairs;
+        let best = freqs.into_iter()
+            .max_by_key(|&(card, count)| (count, rules.rank(card)))
+            .map_or(rules.order.chars().last().unwrap(), |(card, _)| card);
+        self.cards.iter().map(|&c| if Some(c) == rules.wild { best } else { c }).collect()
     }
 }
 
 
 fn main() -> io::Result<()>{
     let args = Args::parse();
+    let rules = Rules::new(&args).map_err(invalid)?;
     let file = File::open(args.input_path)?;
     let reader = BufReader::new(file);
 
     let mut hands = Vec::<Hand>::new();
     for line in reader.lines() {
-        let line = line?;
-        hands.push(
-            Hand::new(
-                line.split(' ').nth(0).unwrap(), 
-                line.split(' ').nth(1).unwrap().parse::<u32>().unwrap()
-            )
-        );
+        hands.push(Hand::new(&line?, &rules).map_err(invalid)?);
     }
     hands.sort_unstable();
-    // println!("{:#?}", hands);
+    if let Some(format) = args.report {
+        report(&hands, &rules, format);
+    }
     let mut res = 0;
     for (i, hand) in hands.into_iter().enumerate() {
         res += (i as u32 + 1) * hand.bid;
@@ -256,3 +165,26 @@ fn main() -> io::Result<()>{
     println!("{}", res);
     Ok(())
 }
+
+
+fn report(hands: &[Hand], rules: &Rules, format: ReportFormat) {
+    match format {
+        ReportFormat::Table => println!("{:>5} {:>8} {:>8} {:<12} {:>5} {:>10}",
+            "rank", "hand", "as", "type", "bid", "winnings"),
+        ReportFormat::Csv => println!("rank,hand,as,type,bid,winnings"),
+    }
+    for (i, hand) in hands.iter().enumerate() {
+        let (rank, cards) = (i + 1, hand.cards.iter().collect::<String>());
+        let (effective, kind, winnings) = (hand.effective(rules), format!("{:?}", hand.kind), rank as u32 * hand.bid);
+        match format {
+            ReportFormat::Table => println!("{:>5} {:>8} {:>8} {:<12} {:>5} {:>10}",
+                rank, cards, effective, kind, hand.bid, winnings),
+            ReportFormat::Csv => println!("{},{},{},{},{},{}", rank, cards, effective, kind, hand.bid, winnings),
+        }
+    }
+}
+
+
+fn invalid(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
@@ -1,8 +1,7 @@
 use std::fs::File;
-use std::io::{self, prelude::*, BufReader};
-use clap::Parser;
-use std::collections::HashMap;
-use itertools::izip;
+use std::io::{self, prelude::*, BufReader, BufWriter};
+use clap::{Parser, ValueEnum};
+use std::collections::{HashMap, HashSet};
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None, help_template = "\
@@ -16,6 +15,263 @@ struct Args {
     /// Input file
     #[arg(short, long = "input-path")]
     input_path: String,
+
+    /// Print the cycle found for every ghost
+    #[arg(short, long)]
+    cycles: bool,
+
+    /// Start node of the single walk
+    #[arg(long, default_value = "AAA")]
+    from: String,
+
+    /// End node of the single walk
+    #[arg(long, default_value = "ZZZ")]
+    to: String,
+
+    /// Ghosts start on every node with this suffix
+    #[arg(long, default_value = "A")]
+    start_suffix: String,
+
+    /// Ghosts stop on every node with this suffix
+    #[arg(long, default_value = "Z")]
+    end_suffix: String,
+
+    /// Print the strongly connected components and which ends each start can reach
+    #[arg(short, long)]
+    analysis: bool,
+
+    /// Export the network to this file
+    #[arg(short, long = "output-path")]
+    output_path: Option<String>,
+
+    /// Format of the exported network
+    #[arg(short, long, value_enum, default_value_t = Format::Dot)]
+    format: Format,
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum Format {
+    Dot, Graphml
+}
+
+#[derive(Debug, Default)]
+struct Network {  // Nodes are interned, edges are indices
+    names: Vec<String>,
+    ids: HashMap<String, usize>,
+    edges: Vec<(usize, usize)>,
+    instructions: Vec<char>,
+}
+
+#[derive(Debug)]
+struct Hit {
+    step: u64,
+    node: usize,
+    instr: usize,
+}
+
+#[derive(Debug)]
+struct Cycle {
+    start: usize,
+    offset: u64,  // Steps before entering the cycle
+    length: u64,
+    hits: Vec<Hit>,  // Every Z node visited until the cycle closes
+}
+
+
+impl Network {
+    fn parse(mut lines: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut network = Network {
+            instructions: lines.next().ok_or("missing instructions")?.chars().collect(),
+            ..Default::default()
+        };
+        if network.instructions.is_empty() {
+            return Err(String::from("missing instructions"));
+        }
+        if let Some(c) = network.instructions.iter().find(|&&c| c != 'L' && c != 'R') {
+            return Err(format!("invalid instruction '{}'", c));
+        }
+        let mut defined = vec![];
+        for line in lines.filter(|l| !l.is_empty()) {
+            let (node, children) = line.split_once(" = ").ok_or(format!("invalid node '{}'", line))?;
+            let (left, right) = children.strip_prefix('(').and_then(|c| c.strip_suffix(')'))
+                .and_then(|c| c.split_once(", "))
+                .ok_or(format!("invalid children '{}'", children))?;
+            let (node, left, right) = (network.intern(node), network.intern(left), network.intern(right));
+            defined.resize(network.names.len(), false);
+            if defined[node] {
+                return Err(format!("node '{}' is defined twice", network.names[node]));
+            }
+            defined[node] = true;
+            network.edges[node] = (left, right);
+        }
+        if let Some(node) = defined.iter().position(|&d| !d) {
+            return Err(format!("node '{}' is referenced but never defined", network.names[node]));
+        }
+        Ok(network)
+    }
+
+    fn intern(&mut self, name: &str) -> usize {
+        if let Some(&id) = self.ids.get(name) {
+            return id;
+        }
+        let id = self.names.len();
+        self.names.push(String::from(name));
+        self.ids.insert(String::from(name), id);
+        self.edges.push((id, id));  // Until its own line is read
+        id
+    }
+
+    fn step(&self, node: usize, instr: usize) -> usize {
+        match self.instructions[instr] {
+            'L' => self.edges[node].0,
+            _ => self.edges[node].1,
+        }
+    }
+
+    fn walk(&self, start: usize, end: usize) -> Option<u64> {  // None if the walk loops without reaching the end
+        let mut seen = HashSet::<(usize, usize)>::new();
+        let mut curr = start;
+        let mut step = 0;
+        while curr != end {
+            let instr = step as usize % self.instructions.len();
+            if !seen.insert((curr, instr)) {
+                return None;
+            }
+            curr = self.step(curr, instr);
+            step += 1;
+        }
+        Some(step)
+    }
+
+    fn reachable(&self, start: usize) -> Vec<bool> {  // Ignoring the instructions
+        let mut visited = vec![false; self.names.len()];
+        let mut stack = vec![start];
+        while let Some(node) = stack.pop() {
+            if visited[node] {
+                continue;
+            }
+            visited[node] = true;
+            stack.push(self.edges[node].0);
+            stack.push(self.edges[node].1);
+        }
+        visited
+    }
+
+    fn sccs(&self) -> Vec<Vec<usize>> {  // Kosaraju, iterative
+        let n = self.names.len();
+        let mut order = vec![];
+        let mut visited = vec![false; n];
+        for root in 0..n {
+            let mut stack = vec![(root, false)];
+            while let Some((node, done)) = stack.pop() {
+                if done {
+                    order.push(node);
+                    continue;
+                }
+                if visited[node] {
+                    continue;
+                }
+                visited[node] = true;
+                stack.push((node, true));
+                stack.push((self.edges[node].0, false));
+                stack.push((self.edges[node].1, false));
+            }
+        }
+        let mut parents = vec![vec![]; n];
+        for (node, &(left, right)) in self.edges.iter().enumerate() {
+            parents[left].push(node);
+            parents[right].push(node);
+        }
+        let mut component = vec![usize::MAX; n];
+        let mut sccs = vec![];
+        for &root in order.iter().rev() {
+            if component[root] != usize::MAX {
+                continue;
+            }
+            let mut scc = vec![];
+            let mut stack = vec![root];
+            component[root] = sccs.len();
+            while let Some(node) = stack.pop() {
+                scc.push(node);
+                for &parent in parents[node].iter() {
+                    if component[parent] == usize::MAX {
+                        component[parent] = sccs.len();
+                        stack.push(parent);
+                    }
+                }
+            }
+            sccs.push(scc);
+        }
+        sccs
+    }
+
+    fn save(&self, path: &str, format: Format) -> io::Result<()> {
+        let f = File::create(path)?;
+        let mut out = BufWriter::new(f);
+        match format {
+            Format::Dot => {
+                let names: Vec<String> = self.names.iter().map(|name| escape_dot(name)).collect();
+                out.write_fmt(format_args!("digraph network {{\n"))?;
+                for (node, &(left, right)) in self.edges.iter().enumerate() {
+                    out.write_fmt(format_args!("  \"{}\" -> \"{}\" [label=\"L\"];\n", names[node], names[left]))?;
+                    out.write_fmt(format_args!("  \"{}\" -> \"{}\" [label=\"R\"];\n", names[node], names[right]))?;
+                }
+                out.write_fmt(format_args!("}}\n"))?;
+            }
+            Format::Graphml => {
+                let names: Vec<String> = self.names.iter().map(|name| escape_xml(name)).collect();
+                out.write_fmt(format_args!("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n"))?;
+                out.write_fmt(format_args!("<graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">\n"))?;
+                out.write_fmt(format_args!("  <key id=\"dir\" for=\"edge\" attr.name=\"direction\" attr.type=\"string\"/>\n"))?;
+                out.write_fmt(format_args!("  <graph id=\"network\" edgedefault=\"directed\">\n"))?;
+                for name in names.iter() {
+                    out.write_fmt(format_args!("    <node id=\"{}\"/>\n", name))?;
+                }
+                for (node, &(left, right)) in self.edges.iter().enumerate() {
+                    for (dir, child) in [("L", left), ("R", right)] {
+                        out.write_fmt(format_args!("    <edge source=\"{}\" target=\"{}\"><data key=\"dir\">{}</data></edge>\n",
+                            names[node], names[child], dir))?;
+                    }
+                }
+                out.write_fmt(format_args!("  </graph>\n</graphml>\n"))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+
+impl Cycle {
+    fn find(network: &Network, start: usize, is_end: impl Fn(&str) -> bool) -> Self {
+        let mut seen = HashMap::<(usize, usize), u64>::new();
+        let mut hits = vec![];
+        let mut curr = start;
+        let mut step = 0;
+        loop {
+            let instr = step as usize % network.instructions.len();
+            if let Some(&first) = seen.get(&(curr, instr)) {
+                return Cycle { start, offset: first, length: step - first, hits };
+            }
+            seen.insert((curr, instr), step);
+            if is_end(&network.names[curr]) {
+                hits.push(Hit { step, node: curr, instr });
+            }
+            curr = network.step(curr, instr);
+            step += 1;
+        }
+    }
+
+    fn hits_at(&self, step: u64) -> bool {
+        if step < self.offset {
+            return self.hits.iter().any(|h| h.step == step);
+        }
+        let residue = (step - self.offset) % self.length;
+        self.hits.iter().any(|h| h.step >= self.offset && h.step - self.offset == residue)
+    }
+
+    fn is_simple(&self) -> bool {  // The structure of the official inputs, where the LCM is enough
+        self.hits.len() == 1 && self.hits[0].step == self.length && self.offset <= self.length
+    }
 }
 
 
@@ -24,58 +280,167 @@ fn main() -> io::Result<()>{
     let file = File::open(args.input_path)?;
     let reader = BufReader::new(file);
 
-    // Input read and parse
-    let mut graph = HashMap::<String, (String, String)>::new();
-    let mut lines = reader.lines();
-    let instructions = lines.next().unwrap()?.chars().collect::<Vec<char>>();
-    let _ = lines.next().unwrap()?;
-    while let Some(line) = lines.next() {
-        let line = line?;
-        *graph.entry(line[..3].to_string()).or_insert((String::new(), String::new())) = (line[7..10].to_string(), line[12..15].to_string());
-    }
+    let lines = reader.lines().collect::<io::Result<Vec<String>>>()?;
+    let network = Network::parse(lines.into_iter())
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
 
-    // Detect entry nodes
-    let mut curr_states: Vec<_> = graph
-        .keys()
-        .filter(|x| x.ends_with('A'))
+    if let Some(path) = &args.output_path {
+        network.save(path, args.format)?;
+    }
+    let starts: Vec<usize> = (0..network.names.len())
+        .filter(|&id| network.names[id].ends_with(&args.start_suffix))
+        .collect();
+    let ends: Vec<usize> = (0..network.names.len())
+        .filter(|&id| network.names[id].ends_with(&args.end_suffix))
         .collect();
-    println!("{}", curr_states.len());
-
-    // Traversal
-    let mut counts: Vec<u32> = vec![0; curr_states.len()];
-    for (curr, count) in izip!(curr_states.iter_mut(), counts.iter_mut()){
-        while !curr.ends_with('Z') {
-            let idx = *count as usize % instructions.len();
-            
-                *curr = match instructions[idx] {
-                    'L' => &mut &graph[*curr].0,
-                    'R' => &mut &graph[*curr].1,
-                    _ => panic!(),
-                };
-            *count += 1;
-        }
-    }
-    let total = mcm(&counts);
-    println!("{}", total);
+    if args.analysis {
+        print_analysis(&network, &starts, &ends);
+    }
+
+    // Single walk
+    match (network.ids.get(&args.from), network.ids.get(&args.to)) {
+        (Some(&from), Some(&to)) => match network.walk(from, to) {
+            Some(steps) => println!("{}", steps),
+            None => println!("The walk from {} never reaches {}", args.from, args.to),
+        },
+        _ => println!("No {} or {} node to walk between", args.from, args.to),
+    }
+
+    // Ghost walks
+    let is_end = |name: &str| name.ends_with(&args.end_suffix);
+    let cycles: Vec<Cycle> = starts.iter().map(|&id| Cycle::find(&network, id, is_end)).collect();
+    if args.cycles {
+        print_cycles(&network, &cycles);
+    }
+    match first_common_hit(&cycles) {
+        Some(total) => println!("{}", total),
+        None if cycles.is_empty() => println!("No start nodes ending in {}", args.start_suffix),
+        None => println!("The ghosts never stand on Z nodes at the same time"),
+    }
     Ok(())
 }
 
 
-fn mcm(vec: &Vec<u32>) -> u64 {
-    let mut mcm = vec[0] as u64;
-    for i in 1..vec.len() {
-        mcm = mcm_binary(mcm as u64, vec[i] as u64);
+fn print_analysis(network: &Network, starts: &[usize], ends: &[usize]) {
+    let sccs = network.sccs();
+    println!("{} strongly connected components", sccs.len());
+    for scc in sccs.iter().filter(|scc| scc.len() > 1) {
+        let mut names: Vec<&str> = scc.iter().map(|&node| network.names[node].as_str()).collect();
+        names.sort_unstable();
+        println!("  {}: {}", names.len(), names.join(" "));
+    }
+    for &start in starts {
+        let visited = network.reachable(start);
+        let reached: Vec<&str> = ends.iter().filter(|&&end| visited[end]).map(|&end| network.names[end].as_str()).collect();
+        match reached.is_empty() {
+            true => println!("{} reaches no end node", network.names[start]),
+            false => println!("{} can reach {}", network.names[start], reached.join(" ")),
+        }
+    }
+}
+
+
+fn print_cycles(network: &Network, cycles: &[Cycle]) {
+    for cycle in cycles {
+        println!("{}: enters the cycle after {} steps, cycle length {}",
+            network.names[cycle.start], cycle.offset, cycle.length);
+        for hit in cycle.hits.iter() {
+            println!("  step {}: {} at instruction {}", hit.step, network.names[hit.node], hit.instr);
+        }
+    }
+}
+
+
+fn first_common_hit(cycles: &[Cycle]) -> Option<u64> {
+    if cycles.is_empty() {  // No ghosts, no answer
+        return None;
+    }
+    if cycles.iter().all(|c| c.is_simple()) {
+        return Some(mcm(&cycles.iter().map(|c| c.length).collect::<Vec<u64>>()));
+    }
+
+    // Before every ghost is inside its cycle, only explicit hits can match
+    let entered = cycles.iter().map(|c| c.offset).max().unwrap_or(0);
+    let mut early: Vec<u64> = cycles.iter()
+        .flat_map(|c| c.hits.iter().map(|h| h.step))
+        .filter(|&step| step < entered)
+        .collect();
+    early.sort_unstable();
+    if let Some(step) = early.into_iter().find(|&step| cycles.iter().all(|c| c.hits_at(step))) {
+        return Some(step);
+    }
+
+    // Afterwards every ghost hits on fixed residues of its cycle length
+    let mut solutions: Vec<(i128, i128)> = vec![(0, 1)];
+    for cycle in cycles {
+        let length = cycle.length as i128;
+        let mut next = vec![];
+        for hit in cycle.hits.iter().filter(|h| h.step >= cycle.offset) {
+            for &(r, m) in solutions.iter() {
+                if let Some(sol) = crt(r, m, hit.step as i128 % length, length) {
+                    next.push(sol);
+                }
+            }
+        }
+        next.sort_unstable();
+        next.dedup();
+        solutions = next;
+    }
+    solutions.into_iter()
+        .map(|(r, m)| {
+            let entered = entered as i128;
+            if r >= entered { r } else { r + (entered - r + m - 1) / m * m }
+        })
+        .min()
+        .map(|step| step as u64)
+}
+
+
+fn crt(r1: i128, m1: i128, r2: i128, m2: i128) -> Option<(i128, i128)> {  // Generalized to non coprime moduli
+    let (g, p, _) = extended_mcd(m1, m2);
+    if (r2 - r1) % g != 0 {
+        return None;
     }
-    return mcm;
+    let lcm = m1 / g * m2;
+    let k = ((r2 - r1) / g * p).rem_euclid(m2 / g);
+    Some(((r1 + m1 * k).rem_euclid(lcm), lcm))
+}
+
+
+fn extended_mcd(a: i128, b: i128) -> (i128, i128, i128) {  // (g, x, y) with a * x + b * y = g
+    if b == 0 {
+        return (a, 1, 0);
+    }
+    let (g, x, y) = extended_mcd(b, a % b);
+    (g, y, x - a / b * y)
+}
+
+
+fn mcm(vec: &[u64]) -> u64 {
+    let mut mcm = vec[0];
+    for &x in vec.iter().skip(1) {
+        mcm = mcm_binary(mcm, x);
+    }
+    mcm
 }
 
 fn mcm_binary(a: u64, b: u64) -> u64 {
-    return a * b / mcd_binary(a, b);
+    a * b / mcd_binary(a, b)
 }
 
 fn mcd_binary(a: u64, b: u64) -> u64 {
     if b == 0 {
         return a;
     }
-    return mcd_binary(b, a % b);
+    mcd_binary(b, a % b)
+}
+
+
+fn escape_dot(name: &str) -> String {  // Inside a quoted DOT id
+    name.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+
+fn escape_xml(name: &str) -> String {  // Inside a double quoted XML attribute
+    name.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
 }
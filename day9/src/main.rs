@@ -14,6 +14,78 @@ struct Args {
     /// Input file
     #[arg(short, long = "input-path")]
     input_path: String,
+
+    /// Steps to extrapolate, positive after the last value and negative before the first (0 is the last value)
+    #[arg(short, long, allow_negative_numbers = true, default_values_t = [1, -1])]
+    steps: Vec<i64>,
+
+    /// Print the difference pyramid, degree and polynomial of every history
+    #[arg(short, long)]
+    pyramid: bool,
+}
+
+#[derive(Debug)]
+struct History {
+    rows: Vec<Vec<i128>>,  // Difference pyramid, the last row is all zeros
+    first_diffs: Vec<i128>,  // First value of every row but the last
+}
+
+
+impl History {
+    fn new(values: &[i128]) -> Result<Self, String> {
+        let mut rows = vec![values.to_vec()];
+        while rows.last().unwrap().iter().any(|&x| x != 0) {
+            let row = rows.last().unwrap();
+            if row.len() == 1 {
+                return Err(format!("the differences of {:?} never reach all zeros", values));
+            }
+            rows.push(row.windows(2).map(|w| w[1] - w[0]).collect());
+        }
+        let first_diffs = rows[..rows.len() - 1].iter().map(|row| row[0]).collect();
+        Ok(History { rows, first_diffs })
+    }
+
+    fn degree(&self) -> usize {  // The zero polynomial is reported as degree 0
+        self.first_diffs.len().saturating_sub(1)
+    }
+
+    fn coefficients(&self) -> Vec<(i128, i128)> {  // Reduced fractions, from x^0 upwards
+        let mut coefs = vec![(0, 1); self.first_diffs.len().max(1)];
+        let mut falling = vec![1];  // x (x - 1) ... (x - j + 1), from x^0 upwards
+        let mut factorial = 1;
+        for (j, &diff) in self.first_diffs.iter().enumerate() {
+            factorial *= j.max(1) as i128;
+            for (k, &c) in falling.iter().enumerate() {
+                coefs[k] = add_fractions(coefs[k], (diff * c, factorial));
+            }
+            let mut next = vec![0; falling.len() + 1];
+            for (k, &c) in falling.iter().enumerate() {
+                next[k + 1] += c;
+                next[k] -= c * j as i128;
+            }
+            falling = next;
+        }
+        coefs
+    }
+
+    fn at(&self, x: i128) -> Option<i128> {  // Newton's forward formula, x = 0 is the first value
+        let mut res: i128 = 0;
+        let mut binomial: i128 = 1;  // C(x, j), exact for any integer x
+        for (j, &diff) in self.first_diffs.iter().enumerate() {
+            res = res.checked_add(diff.checked_mul(binomial)?)?;
+            if j + 1 < self.first_diffs.len() {  // The last binomial is never used and may overflow
+                binomial = binomial.checked_mul(x - j as i128)? / (j as i128 + 1);
+            }
+        }
+        Some(res)
+    }
+
+    fn extrapolate(&self, steps: i64) -> Option<i128> {
+        match steps >= 0 {
+            true => self.at(self.rows[0].len() as i128 - 1 + steps as i128),
+            false => self.at(steps as i128),
+        }
+    }
 }
 
 
@@ -22,41 +94,82 @@ fn main() -> io::Result<()>{
     let file = File::open(args.input_path)?;
     let reader = BufReader::new(file);
 
-    let mut res = 0;
+    let mut histories = vec![];
     for line in reader.lines() {
-        let mut nums = line?
+        let nums = line?
             .split_whitespace()
-            .map(|x| x.parse::<i32>().unwrap())
-            .collect::<Vec<i32>>();
-        res += compute_next(&mut nums);
+            .map(|x| x.parse::<i128>().unwrap())
+            .collect::<Vec<i128>>();
+        histories.push(History::new(&nums).map_err(invalid)?);
+    }
+    if args.pyramid {
+        for history in histories.iter() {
+            print_pyramid(history);
+        }
+    }
+    for &steps in args.steps.iter() {
+        let mut res: i128 = 0;
+        for history in histories.iter() {
+            res = history.extrapolate(steps)
+                .and_then(|x| res.checked_add(x))
+                .ok_or_else(|| invalid(format!("extrapolating {} steps overflows", steps)))?;
+        }
+        println!("{}", res);
     }
-    println!("{}", res);
     Ok(())
 }
 
 
-fn compute_next(nums: &mut Vec<i32>) -> i32 {
-    let next_diff = nums;
-    let mut first_values = vec![];
-    let mut sign = 1;
-    while !all_zeros(next_diff){
-        first_values.push(next_diff[0] * sign);
-        sign *= -1;
-        let diffs: Vec<i32> = next_diff
-            .windows(2)
-            .map(|window| window[1] - window[0])
-            .collect();
-        *next_diff = diffs;
+fn print_pyramid(history: &History) {
+    let width = history.rows.iter().flatten().map(|x| x.to_string().len()).max().unwrap_or(1) + 2;
+    for (j, row) in history.rows.iter().enumerate() {
+        let cells: Vec<String> = row.iter().map(|x| format!("{:>w$}", x, w = width)).collect();
+        println!("{}{}", " ".repeat(j * width / 2), cells.join(""));
+    }
+    let mut poly = String::new();
+    for (k, &(num, den)) in history.coefficients().iter().enumerate().rev() {
+        if num == 0 && !(k == 0 && poly.is_empty()) {
+            continue;
+        }
+        let sign = match (num < 0, poly.is_empty()) {
+            (true, true) => "-",
+            (true, false) => " - ",
+            (false, true) => "",
+            (false, false) => " + ",
+        };
+        let coef = match (num.abs(), den) {
+            (1, 1) if k > 0 => String::new(),  // x rather than 1 x
+            (n, 1) => n.to_string(),
+            (n, d) => format!("{}/{}", n, d),
+        };
+        let space = if coef.is_empty() { "" } else { " " };
+        poly += &match k {
+            0 => format!("{}{}", sign, coef),
+            1 => format!("{}{}{}x", sign, coef, space),
+            _ => format!("{}{}{}x^{}", sign, coef, space, k),
+        };
+    }
+    println!("degree {}: p(x) = {}, x = 0 at the first value", history.degree(), poly);
+    println!();
+}
+
+
+fn add_fractions(a: (i128, i128), b: (i128, i128)) -> (i128, i128) {  // Positive denominators
+    let num = a.0 * b.1 + b.0 * a.1;
+    let den = a.1 * b.1;
+    let g = mcd(num.abs(), den);
+    (num / g, den / g)
+}
+
+
+fn mcd(a: i128, b: i128) -> i128 {
+    if b == 0 {
+        return a;
     }
-    return first_values
-        .into_iter()
-        .fold(0, |acc, x| acc + x);
+    mcd(b, a % b)
 }
 
 
-fn all_zeros(vec: &Vec<i32>) -> bool {
-    return vec
-        .into_iter()
-        .filter(|&&x| x == 0)
-        .count() == vec.len();
+fn invalid(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
 }